@@ -12,52 +12,138 @@ const DIRECTIONS = {
 };
 
 const bootstrap = (modules) => {
-    const {Board} = modules;
-    let board = Board.new(10, 20);
+    const {Ai, Board, GameConfig, GameMode, GameStatus, InputController, Key, PuzzleGoal} = modules;
+    // a T-spin double to practice with ?mode=puzzle
+    const PUZZLE = [
+        "..........",
+        "...#......",
+        "#...######",
+        "##.#######",
+    ].join("\n");
+    const MODES = {
+        'sprint': GameMode.Sprint,
+        'ultra': GameMode.Ultra,
+        'dig': GameMode.Dig,
+        'marathon': GameMode.Marathon,
+        'master': GameMode.Master,
+    };
+    // ?rules=guideline, nes or tgm, the rules of the original game otherwise
+    const RULES = {
+        'guideline': GameConfig.guideline,
+        'nes': GameConfig.classic_nes,
+        'tgm': GameConfig.tgm,
+    };
+    const newBoard = () => {
+        const rules = RULES[new URLSearchParams(location.search).get('rules')];
+        const board = rules === undefined ? Board.guideline() : Board.from_config(rules());
+        if (new URLSearchParams(location.search).get('mode') === 'puzzle') {
+            board.load_puzzle(PUZZLE, "T", PuzzleGoal.TSpinDouble, 1);
+            return board;
+        }
+        const mode = MODES[new URLSearchParams(location.search).get('mode')];
+        board.set_mode(mode === undefined ? GameMode.Endless : mode);
+        return board;
+    };
+    if (new URLSearchParams(location.search).get('mode') === 'versus') {
+        playVersus(modules);
+        return;
+    }
+    if (new URLSearchParams(location.search).get('mode') === 'online') {
+        playOnline(modules);
+        return;
+    }
+    let board = newBoard();
+    let lastTime = performance.now();
+    // ?ai=1 lets the built-in AI play, one key every few frames
+    const ai = new URLSearchParams(location.search).get('ai') ? new Ai() : null;
+    let plan = [];
+    let planned = -1;
+    const playAi = () => {
+        if (plan.length === 0 && board.get_pieces_placed() !== planned) {
+            plan = Array.from(ai.best_inputs(board));
+            if (plan.length > 0) {
+                planned = board.get_pieces_placed();
+            }
+        } else if (plan.length > 0) {
+            board.press(plan.shift());
+        }
+    };
+
+    // DAS and ARR are handled by the engine, the browser key repeat is ignored
+    const input = new InputController();
+    const KEYS = {
+        "Down": Key.SoftDrop, // IE/Edge specific value
+        "ArrowDown": Key.SoftDrop,
+        "j": Key.SoftDrop,
+        " ": Key.HardDrop,
+        "Left": Key.Left, // IE/Edge specific value
+        "ArrowLeft": Key.Left,
+        "h": Key.Left,
+        "Right": Key.Right, // IE/Edge specific value
+        "ArrowRight": Key.Right,
+        "l": Key.Right,
+        "r": Key.Rotate,
+        "c": Key.Hold,
+    };
+
+    // the engine owns the game time, the browser only reports how much time has passed
+    const renderLoop = (now) => {
+        input.update(board, Math.round(now));
+        if (ai !== null) {
+            playAi();
+        }
+        const running = board.update(Math.max(0, Math.round(now - lastTime)));
+        lastTime = now;
 
-    const renderLoop = () => {
         pre.textContent = board.render();
         score.textContent = board.get_score();
         next_shape.textContent = board.get_next_shape_type();
 
-        if (!board.tick()) {
-            alert("Game over! Your score: " + board.get_score());
-            board = Board.new(10, 20);
+        if (!running) {
+            if (board.get_status() === GameStatus.Completed) {
+                const result = board.get_result();
+                alert("Finished in " + (result.get_elapsed_ms() / 1000).toFixed(2) + "s, "
+                    + "score " + result.get_score() + ", "
+                    + result.get_lines() + " lines, level " + result.get_level() + ", "
+                    + result.get_pieces_placed() + " pieces, "
+                    + board.get_pps().toFixed(2) + " PPS, "
+                    + board.get_finesse_faults() + " finesse faults");
+            } else {
+                alert("Game over! Your score: " + board.get_score());
+            }
+            board = newBoard();
+            lastTime = performance.now();
+            plan = [];
+            planned = -1;
         }
 
-        setTimeout(() => {
-            requestAnimationFrame(renderLoop);
-        }, 500);
+        requestAnimationFrame(renderLoop);
     };
 
 
     document.onkeydown = (e) => {
-        const event = e || window.event;
-        switch (e.key) {
-            case "Down": // IE/Edge specific value
-            case "ArrowDown":
-            case "j":
-                board.move_shape(DIRECTIONS.DOWN);
-                break;
-            case "Left": // IE/Edge specific value
-            case "ArrowLeft":
-            case "h":
-                board.move_shape(DIRECTIONS.LEFT);
-                break;
-            case "Right": // IE/Edge specific value
-            case "ArrowRight":
-            case "l":
-                board.move_shape(DIRECTIONS.RIGHT);
-                break;
-            case "r":
-                board.rotate();
-                break;
-            default:
-                return;
+        const key = KEYS[e.key];
+        if (key !== undefined) {
+            if (!e.repeat) {
+                input.key_down(board, key, Math.round(performance.now()));
+            }
+        } else if (e.key === "z") {
+            board.undo();
+        } else if (e.key === "y") {
+            board.redo();
+        } else {
+            return;
         }
         pre.textContent = board.render();
     };
 
+    document.onkeyup = (e) => {
+        const key = KEYS[e.key];
+        if (key !== undefined) {
+            input.key_up(board, key, Math.round(performance.now()));
+        }
+    };
+
     document.getElementById('down').onclick
         = () => board.move_shape(DIRECTIONS.DOWN);
     document.getElementById('left').onclick
@@ -68,4 +154,143 @@ const bootstrap = (modules) => {
         = () => board.rotate();
 
     requestAnimationFrame(renderLoop);
-};
\ No newline at end of file
+};
+// ?mode=versus, two players on one keyboard, the lines they clear are sent as garbage
+const playVersus = (modules) => {
+    const {GameConfig, Key, Match} = modules;
+    const KEYS = [
+        {
+            "a": Key.Left,
+            "d": Key.Right,
+            "s": Key.SoftDrop,
+            "w": Key.HardDrop,
+            "q": Key.Rotate,
+            "e": Key.Hold,
+        },
+        {
+            "ArrowLeft": Key.Left,
+            "ArrowRight": Key.Right,
+            "ArrowDown": Key.SoftDrop,
+            "Enter": Key.HardDrop,
+            ".": Key.Rotate,
+            ",": Key.Hold,
+        },
+    ];
+    const config = GameConfig.guideline();
+    const newMatch = () => {
+        const versus = new Match(KEYS.length, config);
+        // both players get the same shapes
+        versus.set_seed(Math.floor(Math.random() * 0xffffffff));
+        return versus;
+    };
+    let versus = newMatch();
+    let lastTime = performance.now();
+
+    // the boards side by side
+    const render = () => {
+        const boards = KEYS.map((_, player) => versus.render(player).split("\n"));
+        pre.textContent = boards[0].map((row, i) => row + "   " + boards[1][i]).join("\n");
+    };
+
+    const renderLoop = (now) => {
+        const running = versus.update(Math.max(0, Math.round(now - lastTime)), Math.round(now));
+        lastTime = now;
+        render();
+        score.textContent = KEYS.map((_, player) =>
+            "P" + (player + 1) + " " + versus.get_score(player) + " (+" + versus.get_incoming_garbage(player) + ")"
+        ).join("  ");
+
+        if (!running) {
+            const winner = versus.get_winner();
+            alert(winner === undefined ? "Draw!" : "Player " + (winner + 1) + " wins!");
+            versus.free();
+            versus = newMatch();
+            lastTime = performance.now();
+        }
+        requestAnimationFrame(renderLoop);
+    };
+
+    const find = (e) => KEYS.findIndex((keys) => keys[e.key] !== undefined);
+    document.onkeydown = (e) => {
+        const player = find(e);
+        if (player < 0) {
+            return;
+        }
+        e.preventDefault();
+        if (!e.repeat) {
+            versus.key_down(player, KEYS[player][e.key], Math.round(performance.now()));
+        }
+        render();
+    };
+
+    document.onkeyup = (e) => {
+        const player = find(e);
+        if (player >= 0) {
+            versus.key_up(player, KEYS[player][e.key], Math.round(performance.now()));
+        }
+    };
+
+    requestAnimationFrame(renderLoop);
+};
+
+// ?mode=online&server=ws://localhost:9001, a match against other browsers through the relay server
+const playOnline = (modules) => {
+    const {GameConfig, Key, NetClient} = modules;
+    const KEYS = {
+        "ArrowLeft": Key.Left,
+        "ArrowRight": Key.Right,
+        "ArrowDown": Key.SoftDrop,
+        " ": Key.HardDrop,
+        "ArrowUp": Key.Rotate,
+        "c": Key.Hold,
+    };
+    const server = new URLSearchParams(location.search).get('server') || "ws://localhost:9001";
+    const config = GameConfig.guideline();
+    let client = new NetClient(server, config);
+    let lastTime = performance.now();
+
+    const renderLoop = (now) => {
+        const running = client.update(Math.max(0, Math.round(now - lastTime)));
+        lastTime = now;
+        if (!client.is_started()) {
+            pre.textContent = "Waiting for the other players...";
+        } else {
+            // the own board first
+            const me = client.get_player();
+            const players = [me].concat([...Array(client.get_players()).keys()].filter(p => p !== me));
+            const boards = players.map(player => client.render(player).split("\n"));
+            pre.textContent = boards[0].map((row, i) => boards.map(board => board[i]).join("   ")).join("\n");
+            score.textContent = client.get_score(me)
+                + (client.is_desynced() ? " (out of sync)" : "");
+        }
+
+        if (!running) {
+            const winner = client.get_winner();
+            alert(winner === client.get_player() ? "You win!" : "You lose!");
+            client.close();
+            client.free();
+            client = new NetClient(server, config);
+            lastTime = performance.now();
+        }
+        requestAnimationFrame(renderLoop);
+    };
+
+    document.onkeydown = (e) => {
+        const key = KEYS[e.key];
+        if (key !== undefined) {
+            e.preventDefault();
+            if (!e.repeat) {
+                client.key_down(key);
+            }
+        }
+    };
+
+    document.onkeyup = (e) => {
+        const key = KEYS[e.key];
+        if (key !== undefined) {
+            client.key_up(key);
+        }
+    };
+
+    requestAnimationFrame(renderLoop);
+};
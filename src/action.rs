@@ -1,12 +1,14 @@
+use crate::config::RotationSystem;
+use crate::event::*;
 use crate::tetris::*;
 use wasm_bindgen::prelude::*;
 //use web_sys::console;
 
-
 //extern crate web_sys;
 
 #[wasm_bindgen]
 #[repr(u8)] // 1 byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Direction {
     Left = 0,
     Right = 1,
@@ -18,22 +20,30 @@ impl Board {
     // return false if game over, true if continues
     pub fn tick(&mut self) -> bool {
         //  what happens in the next frame
+        if self.is_finished() {
+            return false;
+        }
         let try_drop = self.drop();
-        if !try_drop {
-            //todo: check whether game is already over
-            if !self.is_ith_column_all(0, Cell::Empty) {
+        // a lock out may have happened while dropping
+        if !try_drop && !self.is_game_over() {
+            self.clear_rows();
+            if self.is_finished() {
                 return false;
-            } else {
-                let deleted_row_count = self.check_delete_rows() as i32;
-                self.set_score(self.get_score() + deleted_row_count);
-                //                add a random shape
-                self.add_shape(generate_shape(self.get_next_shape_type()));
             }
+            self.spawn_next_shape();
         }
-        true
+        !self.is_finished()
     }
 
     pub fn move_shape(&mut self, direction: Direction) {
+        if self.get_running_cells().is_empty() {
+            // applied once the next shape spawns
+            self.buffer_move(direction);
+            return;
+        }
+        if direction != Direction::Down {
+            self.record_input();
+        }
         let mut next = self.get_cells().clone();
         let mut new_running_cells = vec![];
 
@@ -43,7 +53,7 @@ impl Board {
                     // can move left
                     let mut can_move_left = true;
                     for (i, j) in self.get_running_cells().iter().cloned() {
-                        if next[i][j - 1] == Cell::Placed {
+                        if next[i][j - 1].is_filled() {
                             can_move_left = false;
                             break;
                         }
@@ -59,6 +69,8 @@ impl Board {
 
                     self.set_cells(next);
                     self.set_running_cells(new_running_cells);
+                    self.set_last_move_rotation(false);
+                    self.push_move_event(direction);
                 }
             }
             Direction::Right => {
@@ -66,7 +78,7 @@ impl Board {
                     let mut can_move_right = true;
                     // can move right
                     for (i, j) in self.get_running_cells().iter().cloned() {
-                        if next[i][j + 1] == Cell::Placed {
+                        if next[i][j + 1].is_filled() {
                             can_move_right = false;
                             break;
                         }
@@ -82,9 +94,16 @@ impl Board {
 
                     self.set_cells(next);
                     self.set_running_cells(new_running_cells);
+                    self.set_last_move_rotation(false);
+                    self.push_move_event(direction);
+                }
+            }
+            Direction::Down => {
+                if !self.get_running_cells().is_empty() {
+                    self.push_move_event(direction);
                 }
+                while self.drop() {}
             }
-            Direction::Down => while self.drop() {},
         }
     }
 
@@ -93,53 +112,50 @@ impl Board {
         // rotate by setting running cells
 
         if self.get_running_cells().is_empty() {
+            self.buffer_rotation();
             return;
         }
+        self.record_input();
 
-        let mut next = self.get_cells().clone();
         let mut this_running_cells = self.get_running_cells().clone();
-        let this_top_left_offset = &self.get_running_shape().top_left_offset.clone();
         this_running_cells.sort();
-
-        let mut top_left_point = (0, 0);
-        let height = self.get_running_shape().height;
+        let height = self.get_running_shape().height as i32;
 
         //        find the position of top left point, in a 3*3 or 4*4 grid of the shape
-        let offset = this_top_left_offset.get(0).unwrap();
-        let offset_0 = ((offset.0 + self.get_height() as i32) as usize) % self.get_height();
-        let offset_1 = ((offset.1 + self.get_width() as i32) as usize) % self.get_width();
-        top_left_point.0 = (this_running_cells[0].0 + offset_0) % self.get_height();
-        top_left_point.1 = (this_running_cells[0].1 + offset_1) % self.get_width();
-
-        let mut next_running_cells = vec![];
-        let mut can_rotate = true;
-        for i in top_left_point.0..top_left_point.0 + height {
-            for j in top_left_point.1..top_left_point.1 + height {
-                if i >= self.get_height() || j >= self.get_width() {
-                    // cannot rotate
-                    return;
-                }
-                if next[i][j] == Cell::Running {
-                    let new_point = (
-                        top_left_point.0 + (j - top_left_point.1),
-                        top_left_point.1 + height - 1 - (i - top_left_point.0),
-                    );
-                    let (temp_x, temp_y) = new_point;
-                    if temp_x >= self.get_height()
-                        || temp_y >= self.get_width()
-                        || next[temp_x][temp_y] == Cell::Placed
-                    {
-                        can_rotate = false;
-                        break;
-                    }
-                    next_running_cells.push(new_point);
-                    next[i][j] = Cell::Empty;
-                    //                    println!("{:#?} => {:#?}", (i, j), new_point);
-                }
-            }
-        }
-        if !can_rotate {
-            return;
+        let offset = self.get_running_shape().top_left_offset[0];
+        let top = this_running_cells[0].0 as i32 + offset.0;
+        let left = this_running_cells[0].1 as i32 + offset.1;
+        let grid_on_board = top >= 0
+            && left >= 0
+            && top + height <= self.get_height() as i32
+            && left + height <= self.get_width() as i32;
+        let rotated: Vec<(i32, i32)> = this_running_cells
+            .iter()
+            .map(|&(i, j)| {
+                (
+                    top + (j as i32 - left),
+                    left + height - 1 - (i as i32 - top),
+                )
+            })
+            .collect();
+
+        // columns to shift the rotated shape by, tried in order
+        let kicks: &[i32] = match self.get_rotation_system() {
+            RotationSystem::Nintendo if grid_on_board => &[0],
+            RotationSystem::Nintendo => &[],
+            RotationSystem::Arika if self.get_running_shape().shape_type == ShapeType::Line => &[0],
+            RotationSystem::Arika => &[0, 1, -1],
+        };
+        let next_running_cells = match kicks
+            .iter()
+            .find_map(|&kick| self.fit_cells(&rotated, kick))
+        {
+            Some(cells) => cells,
+            None => return,
+        };
+        let mut next = self.get_cells().clone();
+        for (i, j) in this_running_cells.iter().cloned() {
+            next[i][j] = Cell::Empty;
         }
         for (i, j) in next_running_cells.iter().cloned() {
             next[i][j] = Cell::Running;
@@ -147,40 +163,59 @@ impl Board {
 
         self.move_top_left_offset_array(); // update offset
         self.set_cells(next);
-        //        println!("can_rotate: {:?}, top_left_point: {:?}, this_running_cells: {:?}, next_running_cells: {:?}", can_rotate, top_left_point, this_running_cells, next_running_cells);
         self.set_running_cells(next_running_cells);
+        self.set_last_move_rotation(true);
+        let shape_type = self.get_running_shape().shape_type;
+        self.push_event(GameEvent::new(EventKind::Rotate, shape_type));
     }
-}
 
-impl Board {
-    fn drop(&mut self) -> bool {
+    pub fn hold(&mut self) {
+        // swap the running shape with the held one, or with the next shape if nothing is held
+        if self.get_running_cells().is_empty() {
+            self.buffer_hold();
+            return;
+        }
+        if self.is_hold_used() || !self.is_hold_enabled() {
+            return;
+        }
+        // the last piece of a puzzle has nothing to swap with
+        if self.get_hold_shape_type().is_none() && self.is_out_of_puzzle_pieces() {
+            return;
+        }
+
         let mut next = self.get_cells().clone();
-        let mut new_running_cells = vec![];
+        for (i, j) in self.get_running_cells().iter().cloned() {
+            next[i][j] = Cell::Empty;
+        }
+        self.set_cells(next);
+        self.set_running_cells(vec![]);
+
+        let shape_type = self.get_running_shape().shape_type;
+        let next_shape = match self.get_hold_shape_type() {
+            Some(hold_shape_type) => generate_shape(hold_shape_type),
+            None => self.generate_next_shape(),
+        };
+        self.set_hold_shape_type(Some(shape_type));
+        self.set_hold_used(true);
+        self.push_event(GameEvent::new(EventKind::Hold, shape_type));
+        self.add_shape(next_shape);
+    }
+}
 
+impl Board {
+    pub(crate) fn drop(&mut self) -> bool {
         if self.get_running_cells().is_empty() {
             return false;
         }
-
-        // check whether can drop
-        for (i, j) in self.get_running_cells().iter().cloned() {
-            if i == self.get_height() - 1 || next[i + 1][j] == Cell::Placed {
-                // cannot drop, turn all the shape into Placed
-                {
-                    for m in 0..self.get_height() {
-                        for n in 0..self.get_width() {
-                            if next[m][n] == Cell::Running {
-                                next[m][n] = Cell::Placed;
-                            }
-                        }
-                    }
-                }
-                self.set_cells(next);
-                self.set_running_cells(new_running_cells);
-                // then return false
-                return false;
-            }
+        if !self.can_drop() {
+            // cannot drop, turn all the shape into Placed
+            self.lock_shape();
+            // then return false
+            return false;
         }
 
+        let mut next = self.get_cells().clone();
+        let mut new_running_cells = vec![];
         for (i, j) in self.get_running_cells().iter().cloned() {
             if next[i][j] == Cell::Running {
                 next[i][j] = Cell::Empty;
@@ -192,25 +227,193 @@ impl Board {
         }
         self.set_cells(next);
         self.set_running_cells(new_running_cells);
+        self.set_last_move_rotation(false);
         true
     }
 
-    fn check_delete_rows(&mut self) -> usize {
-        let mut current = self.get_cells().clone();
+    // the cells shifted by some columns, if they are all on the board and not filled
+    fn fit_cells(&self, cells: &[(i32, i32)], columns: i32) -> Option<Vec<(usize, usize)>> {
+        cells
+            .iter()
+            .map(|&(i, j)| {
+                let j = j + columns;
+                if i < 0 || j < 0 || i >= self.get_height() as i32 || j >= self.get_width() as i32 {
+                    return None;
+                }
+                let cell = (i as usize, j as usize);
+                if self.get_cell(cell.0, cell.1).is_filled() {
+                    return None;
+                }
+                Some(cell)
+            })
+            .collect()
+    }
 
-        current = current
-            .into_iter()
-            .filter(|row| row.iter().any(|&x| x != Cell::Placed))
-            .collect();
-        let deleted_rows = self.get_cells().len() - current.len();
+    pub(crate) fn can_drop(&self) -> bool {
+        let cells = self.get_cells();
+        !self.get_running_cells().is_empty()
+            && self
+                .get_running_cells()
+                .iter()
+                .all(|&(i, j)| i + 1 < self.get_height() && !cells[i + 1][j].is_filled())
+    }
+
+    fn lock_shape(&mut self) {
+        let locked_out = self
+            .get_running_cells()
+            .iter()
+            .all(|&(i, _)| i < self.get_buffer_height());
+        self.set_t_spin(self.is_t_spin_lock());
+        self.check_finesse();
+        let mut next = self.get_cells().clone();
+        for (i, j) in self.get_running_cells().iter().cloned() {
+            next[i][j] = Cell::Placed;
+        }
+        self.set_cells(next);
+        self.set_running_cells(vec![]);
+        self.set_hold_used(false);
+        self.set_pieces_placed(self.get_pieces_placed() + 1);
+        let shape_type = self.get_running_shape().shape_type;
+        let mut piece_counts = *self.get_piece_counts();
+        piece_counts[shape_type as usize] += 1;
+        self.set_piece_counts(piece_counts);
+        self.push_event(GameEvent::new(EventKind::Lock, shape_type));
+        self.record_lock();
+        if locked_out {
+            self.top_out(GameOverReason::LockOut);
+        }
+    }
+
+    // a T rotated into place with at least 3 of the 4 corners around its center filled
+    pub(crate) fn is_t_spin_lock(&self) -> bool {
+        let running_cells = self.get_running_cells();
+        if self.get_running_shape().shape_type != ShapeType::T || !self.is_last_move_rotation() {
+            return false;
+        }
+        // the center is the only cell next to the 3 others
+        let center = running_cells.iter().find(|&&(i, j)| {
+            running_cells
+                .iter()
+                .filter(|&&(x, y)| {
+                    (x == i && (y + 1 == j || j + 1 == y)) || (y == j && (x + 1 == i || i + 1 == x))
+                })
+                .count()
+                == 3
+        });
+        let (i, j) = match center {
+            Some(&center) => (center.0 as i32, center.1 as i32),
+            None => return false,
+        };
+        let filled_corners = [
+            (i - 1, j - 1),
+            (i - 1, j + 1),
+            (i + 1, j - 1),
+            (i + 1, j + 1),
+        ]
+        .iter()
+        .filter(|&&(x, y)| {
+            // the walls and the floor count as filled
+            x < 0
+                || y < 0
+                || x >= self.get_height() as i32
+                || y >= self.get_width() as i32
+                || self.get_cell(x as usize, y as usize).is_filled()
+        })
+        .count();
+        filled_corners >= 3
+    }
+
+    // delete the full rows after a lock, return the number of deleted rows
+    pub(crate) fn clear_rows(&mut self) -> usize {
+        let level = self.get_level();
+        let deleted_row_count = self.check_delete_rows();
+        let points = self
+            .get_scoring()
+            .score(deleted_row_count, level, self.is_t_spin());
+        self.set_score(self.get_score() + points);
+        self.advance_level_on_clear(deleted_row_count as u32);
+        self.record_puzzle_clear(deleted_row_count);
+        self.record_line_clear(deleted_row_count);
+        self.check_mode_goal();
+        deleted_row_count
+    }
+
+    pub(crate) fn spawn_next_shape(&mut self) {
+        if self.is_out_of_puzzle_pieces() {
+            self.top_out(GameOverReason::OutOfPieces);
+            return;
+        }
+        self.refill_garbage();
+        self.insert_incoming_garbage();
+        if self.is_finished() {
+            return;
+        }
+        //                add a random shape, which can block out
+        let mut next_shape = self.generate_next_shape();
+        // initial hold, the shape goes to the hold before it spawns
+        if self.take_buffered_hold() && self.is_hold_enabled() {
+            let hold_shape_type = self.get_hold_shape_type();
+            if hold_shape_type.is_some() || !self.is_out_of_puzzle_pieces() {
+                let shape_type = next_shape.shape_type;
+                next_shape = match hold_shape_type {
+                    Some(hold_shape_type) => generate_shape(hold_shape_type),
+                    None => self.generate_next_shape(),
+                };
+                self.set_hold_shape_type(Some(shape_type));
+                self.set_hold_used(true);
+                self.push_event(GameEvent::new(EventKind::Hold, shape_type));
+            }
+        }
+        self.add_shape(next_shape);
+        self.advance_level_on_spawn();
+    }
+
+    pub(crate) fn get_full_rows(&self) -> Vec<usize> {
+        (0..self.get_height())
+            .filter(|&i| self.get_cells()[i].iter().all(|x| x.is_filled()))
+            .collect()
+    }
+
+    fn check_delete_rows(&mut self) -> usize {
+        let full_rows = self.get_full_rows();
+        let deleted_rows = full_rows.len();
 
         if deleted_rows > 0 {
+            let garbage_rows = full_rows
+                .iter()
+                .filter(|&&i| self.get_cells()[i].contains(&Cell::Garbage))
+                .count() as u32;
+            self.set_garbage_cleared(self.get_garbage_cleared() + garbage_rows);
+
+            let mut current = self.get_cells().clone();
+            current.retain(|row| row.iter().any(|x| !x.is_filled()));
             let mut next = vec![vec![Cell::Empty; self.get_width()]; deleted_rows];
             next.append(&mut current);
             self.set_cells(next);
+
+            let level = self.get_level();
+            self.set_lines(self.get_lines() + deleted_rows as u32);
+            let shape_type = self.get_running_shape().shape_type;
+            self.push_event(GameEvent::new(EventKind::LineClear, shape_type).with_rows(&full_rows));
+            if self.get_level() > level {
+                self.push_event(
+                    GameEvent::new(EventKind::LevelUp, shape_type).with_level(self.get_level()),
+                );
+            }
         }
         deleted_rows
     }
+
+    pub(crate) fn top_out(&mut self, reason: GameOverReason) {
+        self.set_game_over_reason(Some(reason));
+        let shape_type = self.get_running_shape().shape_type;
+        self.push_event(GameEvent::new(EventKind::GameOver, shape_type));
+    }
+
+    fn push_move_event(&mut self, direction: Direction) {
+        let shape_type = self.get_running_shape().shape_type;
+        self.push_event(GameEvent::new(EventKind::Move, shape_type).with_direction(direction));
+    }
 }
 
 #[cfg(test)]
@@ -261,6 +464,48 @@ mod tests {
         assert!(true);
     }
 
+    #[test]
+    fn test_tick_block_out() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        for i in 1..10 {
+            board.set_cell(i, 0, Cell::Placed);
+        }
+        board.set_cell(2, 3, Cell::Placed);
+        assert!(board.tick());
+        // the square lands on row 0-1, the next one cannot spawn
+        assert!(!board.tick());
+        assert_eq!(board.get_game_over_reason(), Some(GameOverReason::BlockOut));
+    }
+
+    #[test]
+    fn test_tick_lock_out() {
+        let mut board = Board::try_new(8, 10, 2).unwrap();
+        board.set_next_shape_type(ShapeType::Square);
+        for j in 2..6 {
+            board.set_cell(2, j, Cell::Placed);
+        }
+        assert!(board.tick());
+        // the square spawns in the buffer rows and cannot enter the visible field
+        assert!(!board.tick());
+        assert_eq!(board.get_game_over_reason(), Some(GameOverReason::LockOut));
+        assert_eq!(*board.get_cell(0, 3), Cell::Placed);
+    }
+
+    #[test]
+    fn test_tick_partly_visible_lock() {
+        let mut board = Board::try_new(8, 10, 2).unwrap();
+        board.set_next_shape_type(ShapeType::Square);
+        for j in 2..6 {
+            board.set_cell(3, j, Cell::Placed);
+        }
+        assert!(board.tick());
+        assert!(board.tick());
+        // locks on row 1-2, which is not a lock out as one row is visible, but the next square cannot spawn
+        assert!(!board.tick());
+        assert_eq!(board.get_game_over_reason(), Some(GameOverReason::BlockOut));
+    }
+
     #[test]
     fn test_move_shape() {
         let mut board = Board::new(8, 10);
@@ -359,4 +604,26 @@ mod tests {
         test_rotate_same(ShapeType::Square);
         test_rotate_same(ShapeType::L);
     }
+
+    #[test]
+    fn test_rotate_kick() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::T);
+        board.tick();
+        board.tick();
+        // the stem of the rotated T would go into the block above
+        board.set_cell(0, 3, Cell::Placed);
+        let spawn = board.get_running_cells().clone();
+        let mut arika = board.clone();
+        board.rotate();
+        assert_eq!(*board.get_running_cells(), spawn);
+
+        // kicked one column to the right
+        arika.set_rotation_system(RotationSystem::Arika);
+        arika.rotate();
+        let mut cells = arika.get_running_cells().clone();
+        cells.sort();
+        assert_eq!(cells, vec![(0, 4), (1, 3), (1, 4), (2, 4)]);
+        assert!(arika.is_last_move_rotation());
+    }
 }
@@ -0,0 +1,377 @@
+use crate::action::Direction;
+use crate::input::Key;
+use crate::search::Placement;
+use crate::tetris::*;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+// weights of the board features, a placement with a higher score is better
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Heuristic {
+    // height where the shape landed, from the floor
+    pub landing_height: f64,
+    // cleared lines times the cells of the shape in them
+    pub eroded_cells: f64,
+    // changes between filled and empty cells along the rows and the columns
+    pub row_transitions: f64,
+    pub column_transitions: f64,
+    // empty cells with a filled cell above them
+    pub holes: f64,
+    // sum of the well depths, counted as 1 + 2 + .. + depth
+    pub wells: f64,
+    // sum of the column heights
+    pub aggregate_height: f64,
+    // sum of the height differences of the columns next to each other
+    pub bumpiness: f64,
+    pub lines: f64,
+}
+
+#[wasm_bindgen]
+impl Heuristic {
+    // Pierre Dellacherie's features with the El-Tetris weights
+    pub fn el_tetris() -> Heuristic {
+        Heuristic {
+            landing_height: -4.500_158_825_082_766,
+            eroded_cells: 3.418_126_810_139_269_4,
+            row_transitions: -3.217_888_286_848_775_3,
+            column_transitions: -9.348_695_305_445_199,
+            holes: -7.899_265_427_351_652,
+            wells: -3.385_597_224_726_362_6,
+            aggregate_height: 0.0,
+            bumpiness: 0.0,
+            lines: 0.0,
+        }
+    }
+
+    // the genetic algorithm weights by Yiyuan Lee
+    pub fn yiyuan_lee() -> Heuristic {
+        Heuristic {
+            landing_height: 0.0,
+            eroded_cells: 0.0,
+            row_transitions: 0.0,
+            column_transitions: 0.0,
+            holes: -0.356_63,
+            wells: 0.0,
+            aggregate_height: -0.510_066,
+            bumpiness: -0.184_483,
+            lines: 0.760_666,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("heuristic is always serializable")
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl Heuristic {
+    // weights saved by the tuner, missing weights come from the El-Tetris ones
+    pub fn from_json(json: &str) -> Result<Heuristic, JsValue> {
+        Heuristic::try_from_json(json).map_err(JsValue::from)
+    }
+}
+
+// number of weights in a heuristic
+pub const WEIGHTS: usize = 9;
+
+impl Heuristic {
+    // el-tetris or yiyuan-lee
+    pub fn preset(name: &str) -> Option<Heuristic> {
+        match name {
+            "el-tetris" => Some(Heuristic::el_tetris()),
+            "yiyuan-lee" => Some(Heuristic::yiyuan_lee()),
+            _ => None,
+        }
+    }
+
+    pub fn try_from_json(json: &str) -> Result<Heuristic, TetrisError> {
+        serde_json::from_str(json).map_err(|_| TetrisError::InvalidParam)
+    }
+
+    // the weights in the order of the fields
+    pub fn to_weights(&self) -> [f64; WEIGHTS] {
+        [
+            self.landing_height,
+            self.eroded_cells,
+            self.row_transitions,
+            self.column_transitions,
+            self.holes,
+            self.wells,
+            self.aggregate_height,
+            self.bumpiness,
+            self.lines,
+        ]
+    }
+
+    pub fn from_weights(weights: &[f64; WEIGHTS]) -> Heuristic {
+        Heuristic {
+            landing_height: weights[0],
+            eroded_cells: weights[1],
+            row_transitions: weights[2],
+            column_transitions: weights[3],
+            holes: weights[4],
+            wells: weights[5],
+            aggregate_height: weights[6],
+            bumpiness: weights[7],
+            lines: weights[8],
+        }
+    }
+}
+
+impl Default for Heuristic {
+    fn default() -> Self {
+        Heuristic::el_tetris()
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct Ai {
+    heuristic: Heuristic,
+}
+
+#[wasm_bindgen]
+impl Ai {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Ai {
+        Ai::default()
+    }
+
+    pub fn with_heuristic(heuristic: Heuristic) -> Ai {
+        Ai { heuristic }
+    }
+
+    pub fn get_heuristic(&self) -> Heuristic {
+        self.heuristic
+    }
+
+    pub fn set_heuristic(&mut self, heuristic: Heuristic) {
+        self.heuristic = heuristic;
+    }
+
+    // keys to press for the best placement of the running shape, as `Key` values
+    pub fn best_inputs(&self, board: &Board) -> Vec<u8> {
+        self.best_placement(board)
+            .map(|placement| placement.get_inputs())
+            .unwrap_or_default()
+    }
+}
+
+impl Ai {
+    pub fn best_placement(&self, board: &Board) -> Option<Placement> {
+        board
+            .find_placements()
+            .into_iter()
+            .map(|placement| (self.evaluate(board, &placement), placement))
+            .fold(
+                None,
+                |best: Option<(f64, Placement)>, (score, placement)| match best {
+                    Some(best) if best.0 >= score => Some(best),
+                    _ => Some((score, placement)),
+                },
+            )
+            .map(|(_, placement)| placement)
+    }
+
+    // lock the shape at the placement on a copy of the board and score the result
+    pub fn evaluate(&self, board: &Board, placement: &Placement) -> f64 {
+        let mut board = board.clone_without_history();
+        // lock the shape by hand, there is no need for the events and the next shape
+        for &(i, j) in board.get_running_cells().clone().iter() {
+            board.set_cell(i, j, Cell::Empty);
+        }
+        for &(i, j) in placement.cells() {
+            board.set_cell(i, j, Cell::Placed);
+        }
+        board.set_running_cells(vec![]);
+        self.score(&mut board, placement.cells())
+    }
+
+    fn score(&self, board: &mut Board, cells: &[(usize, usize)]) -> f64 {
+        let height = board.get_height();
+        let full_rows = board.get_full_rows();
+        let shape_cells_cleared = cells.iter().filter(|(i, _)| full_rows.contains(i)).count();
+        let landing_height = cells.iter().map(|&(i, _)| (height - i) as f64).sum::<f64>()
+            / cells.len().max(1) as f64;
+        let lines = full_rows.len();
+        board.clear_rows();
+        let features = Features::of(board);
+        let h = &self.heuristic;
+        h.landing_height * landing_height
+            + h.eroded_cells * (lines * shape_cells_cleared) as f64
+            + h.row_transitions * features.row_transitions as f64
+            + h.column_transitions * features.column_transitions as f64
+            + h.holes * features.holes as f64
+            + h.wells * features.wells as f64
+            + h.aggregate_height * features.aggregate_height as f64
+            + h.bumpiness * features.bumpiness as f64
+            + h.lines * lines as f64
+    }
+}
+
+// features of a board without a running shape
+#[derive(Debug, Default, PartialEq)]
+struct Features {
+    row_transitions: usize,
+    column_transitions: usize,
+    holes: usize,
+    wells: usize,
+    aggregate_height: usize,
+    bumpiness: usize,
+}
+
+impl Features {
+    fn of(board: &Board) -> Features {
+        let cells = board.get_cells();
+        let (height, width) = (board.get_height(), board.get_width());
+        let filled = |i: usize, j: usize| cells[i][j].is_filled();
+        let mut features = Features::default();
+
+        // the walls count as filled, the row above the stack as empty
+        for i in 0..height {
+            let mut last = true;
+            for j in 0..width {
+                if filled(i, j) != last {
+                    features.row_transitions += 1;
+                }
+                last = filled(i, j);
+            }
+            if !last {
+                features.row_transitions += 1;
+            }
+        }
+
+        let mut heights = vec![0; width];
+        for (j, column_height) in heights.iter_mut().enumerate() {
+            let mut last = false;
+            for i in 0..height {
+                if filled(i, j) != last {
+                    features.column_transitions += 1;
+                }
+                last = filled(i, j);
+                if filled(i, j) && *column_height == 0 {
+                    *column_height = height - i;
+                }
+                if !filled(i, j) && *column_height > 0 {
+                    features.holes += 1;
+                }
+            }
+            // the floor is filled
+            if !last {
+                features.column_transitions += 1;
+            }
+
+            let mut depth = 0;
+            for i in 0..height {
+                let left = j == 0 || filled(i, j - 1);
+                let right = j + 1 == width || filled(i, j + 1);
+                if !filled(i, j) && left && right {
+                    depth += 1;
+                    features.wells += depth;
+                } else {
+                    depth = 0;
+                }
+            }
+        }
+
+        features.aggregate_height = heights.iter().sum();
+        features.bumpiness = heights
+            .windows(2)
+            .map(|pair| pair[0].max(pair[1]) - pair[0].min(pair[1]))
+            .sum();
+        features
+    }
+}
+
+#[wasm_bindgen]
+impl Board {
+    // one key of an input sequence, a soft drop goes to the floor without locking
+    pub fn press(&mut self, key: Key) {
+        match key {
+            Key::Left => self.move_shape(Direction::Left),
+            Key::Right => self.move_shape(Direction::Right),
+            Key::HardDrop => self.move_shape(Direction::Down),
+            Key::Rotate => self.rotate(),
+            Key::Hold => self.hold(),
+            Key::SoftDrop => {
+                while self.can_drop() {
+                    self.drop();
+                }
+            }
+        }
+    }
+}
+
+impl Board {
+    pub fn play_inputs(&mut self, inputs: &[Key]) {
+        for &key in inputs {
+            self.press(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_features() {
+        let mut board = Board::new(4, 4);
+        // |x| |x| |
+        // |x|x|x| |
+        // | |x| | |
+        for &(i, j) in [(1, 0), (1, 2), (2, 0), (2, 1), (2, 2), (3, 1)].iter() {
+            board.set_cell(i, j, Cell::Placed);
+        }
+        let features = Features::of(&board);
+        assert_eq!(features.holes, 2);
+        assert_eq!(features.aggregate_height, 3 + 2 + 3);
+        assert_eq!(features.bumpiness, 1 + 1 + 3);
+        // the right column is a well 2 cells deep, the cells between two filled ones are wells too
+        assert_eq!(features.wells, 1 + 2 + 1 + 1);
+        assert_eq!(features.row_transitions, 2 + 4 + 2 + 4);
+        assert_eq!(features.column_transitions, 3 + 1 + 3 + 1);
+    }
+
+    #[test]
+    fn test_fills_the_well() {
+        let mut board = Board::new(10, 10);
+        for i in 6..10 {
+            for j in 0..9 {
+                board.set_cell(i, j, Cell::Placed);
+            }
+        }
+        board.set_next_shape_type(ShapeType::Line);
+        board.tick();
+        for heuristic in [Heuristic::el_tetris(), Heuristic::yiyuan_lee()].iter() {
+            let placement = Ai::with_heuristic(*heuristic)
+                .best_placement(&board)
+                .unwrap();
+            assert!(placement.cells().iter().all(|&(_, j)| j == 9));
+            assert_eq!(placement.inputs().last(), Some(&Key::HardDrop));
+
+            let mut played = board.clone();
+            played.play_inputs(placement.inputs());
+            played.tick();
+            assert_eq!(played.get_lines(), 4);
+        }
+    }
+
+    #[test]
+    fn test_plays_a_game() {
+        let mut board = Board::guideline();
+        board.set_seed(7);
+        board.set_undo_enabled(false);
+        let ai = Ai::new();
+        for _ in 0..200 {
+            assert!(board.tick());
+            let inputs = ai.best_inputs(&board);
+            assert!(!inputs.is_empty());
+            board.play_inputs(ai.best_placement(&board).unwrap().inputs());
+        }
+        assert!(board.get_lines() > 50);
+    }
+}
@@ -0,0 +1,42 @@
+// relays networked versus matches between browsers, one match after the other, for example
+// cargo run --release --bin relay -- --port 9001 --players 2
+// and open the game with ?mode=online&server=ws://localhost:9001 in every browser
+use std::env;
+use std::net::TcpListener;
+use tetris_mk::cli::{exit, exit_with_usage, parse};
+use tetris_mk::relay::Relay;
+
+const USAGE: &str = "usage: relay [--port N] [--players N] [--seed N]";
+
+fn main() {
+    let mut port: u16 = 9001;
+    let mut players = 2;
+    let mut seed = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| exit_with_usage(USAGE));
+        match arg.as_str() {
+            "--port" => port = parse(&value, USAGE),
+            "--players" => players = parse(&value, USAGE),
+            "--seed" => seed = Some(parse(&value, USAGE)),
+            _ => exit_with_usage(USAGE),
+        }
+    }
+    if !(2..=255).contains(&players) {
+        exit_with_usage(USAGE);
+    }
+
+    let listener = TcpListener::bind(("0.0.0.0", port)).unwrap_or_else(|err| exit("relay", err));
+    loop {
+        // a new seed for every match unless one is given
+        let relay = Relay {
+            players,
+            seed: seed.unwrap_or_else(rand::random),
+        };
+        println!("waiting for {} players on port {}", players, port);
+        relay
+            .serve_match(&listener)
+            .unwrap_or_else(|err| exit("relay", err));
+        println!("match over");
+    }
+}
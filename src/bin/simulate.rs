@@ -0,0 +1,45 @@
+// plays seeded games with the AI and prints the averages, for example
+// cargo run --release --bin simulate -- --games 1000 --rules nes --heuristic yiyuan-lee
+use std::env;
+use tetris_mk::ai::{Ai, Heuristic};
+use tetris_mk::cli::{exit, exit_with_usage, parse, read_heuristic, read_rules};
+use tetris_mk::sim::{Simulation, Summary};
+
+const USAGE: &str = "usage: simulate [--games N] [--seed N] [--max-pieces N] [--threads N] \
+                     [--rules guideline|nes|tgm|<json file>] \
+                     [--heuristic el-tetris|yiyuan-lee|<json file>]";
+
+fn main() {
+    let mut simulation = Simulation::default();
+    let mut heuristic = Heuristic::el_tetris();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| exit_with_usage(USAGE));
+        match arg.as_str() {
+            "--games" => simulation.games = parse(&value, USAGE),
+            "--seed" => simulation.first_seed = parse(&value, USAGE),
+            "--max-pieces" => simulation.max_pieces = parse(&value, USAGE),
+            "--threads" => simulation.threads = parse(&value, USAGE),
+            "--rules" => simulation.config = read_rules(&value),
+            "--heuristic" => heuristic = read_heuristic(&value),
+            _ => exit_with_usage(USAGE),
+        }
+    }
+
+    let stats = simulation
+        .run(move || Ai::with_heuristic(heuristic))
+        .unwrap_or_else(|err| exit("simulate", err));
+    let summary = Summary::of(&stats);
+    println!("games          {}", summary.games);
+    println!("lines          {:.2}", summary.average_lines);
+    println!("score          {:.2}", summary.average_score);
+    println!("pieces         {:.2}", summary.average_pieces);
+    println!("block outs     {}", summary.block_outs);
+    println!("lock outs      {}", summary.lock_outs);
+    println!("top outs       {}", summary.top_outs);
+    println!("out of pieces  {}", summary.out_of_pieces);
+    println!(
+        "stopped at     {} pieces: {}",
+        simulation.max_pieces, summary.stopped
+    );
+}
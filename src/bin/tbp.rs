@@ -0,0 +1,137 @@
+// plays a game with a Tetris Bot Protocol bot, for example
+// cargo run --release --bin tbp -- --pieces 500 -- cold-clear
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{ChildStdin, Command, Stdio};
+use tetris_mk::cli::{exit, exit_with_usage, parse};
+use tetris_mk::tbp::{BotMessage, FrontendMessage, Piece};
+use tetris_mk::tetris::{Board, ShapeType};
+
+// pieces the bot sees after the running one
+const PREVIEWS: usize = 5;
+
+const USAGE: &str = "usage: tbp [--seed N] [--pieces N] -- <bot command> [bot args]";
+
+fn main() {
+    let mut seed = 0;
+    let mut max_pieces = 1000;
+    let mut args = env::args().skip(1);
+    let mut command = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = parse(&args.next().unwrap_or_default(), USAGE),
+            "--pieces" => max_pieces = parse(&args.next().unwrap_or_default(), USAGE),
+            "--" => command.extend(args.by_ref()),
+            _ => exit_with_usage(USAGE),
+        }
+    }
+    if command.is_empty() {
+        exit_with_usage(USAGE);
+    }
+
+    let mut bot = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|err| exit(&command[0], err));
+    let mut to_bot = bot.stdin.take().expect("stdin is piped");
+    let mut from_bot = BufReader::new(bot.stdout.take().expect("stdout is piped")).lines();
+    let mut receive = move || -> BotMessage {
+        let line = match from_bot.next() {
+            Some(Ok(line)) => line,
+            _ => exit("bot", "closed its output"),
+        };
+        match serde_json::from_str(&line) {
+            Ok(BotMessage::Error { reason }) => exit("bot", reason),
+            Ok(message) => message,
+            Err(err) => exit(&line, err),
+        }
+    };
+
+    match receive() {
+        BotMessage::Info {
+            name,
+            version,
+            author,
+            ..
+        } => println!("playing {} {} by {}", name, version, author),
+        message => exit("expected info", format!("{:?}", message)),
+    }
+    send(&mut to_bot, &FrontendMessage::Rules);
+    if receive() != BotMessage::Ready {
+        exit("bot", "not ready");
+    }
+
+    // the board only draws random shapes, so the pieces the bot sees ahead come from here
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut queue: VecDeque<ShapeType> = (0..PREVIEWS).map(|_| rng.gen()).collect();
+    let mut board = Board::guideline();
+    board.set_undo_enabled(false);
+    board.set_next_shape_type(rng.gen());
+    board.tick();
+    board.set_next_shape_type(queue[0]);
+    let mut start = board.tbp_start();
+    start.queue.extend(
+        queue
+            .iter()
+            .skip(1)
+            .filter_map(|&shape_type| Piece::from_shape_type(shape_type)),
+    );
+    send(&mut to_bot, &FrontendMessage::Start(start));
+    while board.get_pieces_placed() < max_pieces && !board.is_finished() {
+        send(&mut to_bot, &FrontendMessage::Suggest);
+        let moves = match receive() {
+            BotMessage::Suggestion { moves } => moves,
+            message => exit("expected suggestion", format!("{:?}", message)),
+        };
+        // the first move the engine can play, in the order the bot likes them
+        let hold_was_empty = board.get_hold_shape_type().is_none();
+        let running = board.get_running_shape_type();
+        let mv = moves
+            .into_iter()
+            .find(|mv| board.play_tbp_move(mv).is_ok())
+            .unwrap_or_else(|| exit("bot", "no suggested move can be played"));
+        send(&mut to_bot, &FrontendMessage::Play { mv });
+
+        // holding into an empty hold uses up the next piece
+        if hold_was_empty && mv.location.piece.shape_type() != running {
+            queue.pop_front();
+        }
+        board.set_next_shape_type(
+            queue
+                .pop_front()
+                .expect("the queue is refilled every piece"),
+        );
+        board.tick();
+        board.take_events();
+        while queue.len() < PREVIEWS {
+            let shape_type = rng.gen();
+            queue.push_back(shape_type);
+            if let Some(piece) = Piece::from_shape_type(shape_type) {
+                send(&mut to_bot, &FrontendMessage::NewPiece { piece });
+            }
+        }
+        board.set_next_shape_type(queue[0]);
+    }
+    send(&mut to_bot, &FrontendMessage::Stop);
+    send(&mut to_bot, &FrontendMessage::Quit);
+    let _ = bot.wait();
+
+    println!("lines   {}", board.get_lines());
+    println!("score   {}", board.get_score());
+    println!("pieces  {}", board.get_pieces_placed());
+    match board.get_game_over_reason() {
+        Some(reason) => println!("topped out: {:?}", reason),
+        None => println!("stopped at {} pieces", max_pieces),
+    }
+}
+
+fn send(to_bot: &mut ChildStdin, message: &FrontendMessage) {
+    let json = serde_json::to_string(message).expect("messages are always serializable");
+    writeln!(to_bot, "{}", json)
+        .and_then(|_| to_bot.flush())
+        .unwrap_or_else(|err| exit("bot", err));
+}
@@ -0,0 +1,55 @@
+// tunes the AI weights with the cross-entropy method and saves the best ones, for example
+// cargo run --release --bin tune -- --generations 50 --out weights.json
+// the weights can then be played with `simulate --heuristic weights.json`
+use std::env;
+use std::fs;
+use tetris_mk::ai::Heuristic;
+use tetris_mk::cli::{exit, exit_with_usage, parse, read_heuristic, read_rules};
+use tetris_mk::tune::Tuner;
+
+const USAGE: &str = "usage: tune [--generations N] [--population N] [--elite N] [--games N] \
+                     [--max-pieces N] [--seed N] [--threads N] \
+                     [--rules guideline|nes|tgm|<json file>] \
+                     [--start el-tetris|yiyuan-lee|<json file>] [--out <json file>]";
+
+fn main() {
+    let mut tuner = Tuner::default();
+    let mut start = Heuristic::el_tetris();
+    let mut out = String::from("weights.json");
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| exit_with_usage(USAGE));
+        match arg.as_str() {
+            "--generations" => tuner.generations = parse(&value, USAGE),
+            "--population" => tuner.population = parse(&value, USAGE),
+            "--elite" => tuner.elite = parse(&value, USAGE),
+            "--games" => tuner.simulation.games = parse(&value, USAGE),
+            "--max-pieces" => tuner.simulation.max_pieces = parse(&value, USAGE),
+            "--seed" => {
+                tuner.seed = parse(&value, USAGE);
+                tuner.simulation.first_seed = parse(&value, USAGE);
+            }
+            "--threads" => tuner.simulation.threads = parse(&value, USAGE),
+            "--rules" => tuner.simulation.config = read_rules(&value),
+            "--start" => start = read_heuristic(&value),
+            "--out" => out = value,
+            _ => exit_with_usage(USAGE),
+        }
+    }
+
+    let mut best_lines = f64::NEG_INFINITY;
+    let best = tuner
+        .run(start, |generation| {
+            println!(
+                "generation {:3}  best {:8.2}  elite {:8.2} lines",
+                generation.index, generation.best_lines, generation.elite_lines
+            );
+            // save as it goes, tuning takes a while
+            if generation.best_lines > best_lines {
+                best_lines = generation.best_lines;
+                fs::write(&out, generation.best.to_json()).unwrap_or_else(|err| exit(&out, err));
+            }
+        })
+        .unwrap_or_else(|err| exit("tune", err));
+    println!("best {:.2} lines, saved to {}", best.best_lines, out);
+}
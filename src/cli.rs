@@ -0,0 +1,41 @@
+use crate::ai::Heuristic;
+use crate::config::GameConfig;
+use std::fmt::Display;
+use std::fs;
+use std::process;
+use std::str::FromStr;
+
+// helpers of the command line tools in src/bin, errors are printed and end the process
+
+// a flag value, or the usage when it is missing or does not parse
+pub fn parse<T: FromStr>(value: &str, usage: &str) -> T {
+    value.parse().unwrap_or_else(|_| exit_with_usage(usage))
+}
+
+// a preset name or a JSON file of rules
+pub fn read_rules(value: &str) -> GameConfig {
+    GameConfig::preset(value).unwrap_or_else(|| {
+        GameConfig::try_from_json(&read(value)).unwrap_or_else(|err| exit(value, err))
+    })
+}
+
+// a preset name or a JSON file of weights
+pub fn read_heuristic(value: &str) -> Heuristic {
+    Heuristic::preset(value).unwrap_or_else(|| {
+        Heuristic::try_from_json(&read(value)).unwrap_or_else(|err| exit(value, err))
+    })
+}
+
+pub fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|err| exit(path, err))
+}
+
+pub fn exit(context: &str, err: impl Display) -> ! {
+    eprintln!("{}: {}", context, err);
+    process::exit(1);
+}
+
+pub fn exit_with_usage(usage: &str) -> ! {
+    eprintln!("{}", usage);
+    process::exit(2);
+}
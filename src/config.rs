@@ -0,0 +1,408 @@
+use crate::mode::{master_delays, master_gravity};
+use crate::tetris::*;
+use crate::timing::*;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[repr(u8)] // 1 byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScoringSystem {
+    // one point per line
+    Lines = 0,
+    // 100, 300, 500 and 800 times the level, more for T-spins
+    Guideline = 1,
+    // 40, 100, 300 and 1200 times the level
+    ClassicNes = 2,
+}
+
+impl ScoringSystem {
+    pub fn score(self, deleted_rows: usize, level: u32, t_spin: bool) -> i32 {
+        let rows = deleted_rows.min(4);
+        let points = match self {
+            ScoringSystem::Lines => return deleted_rows as i32,
+            ScoringSystem::Guideline if t_spin => [400, 800, 1200, 1600, 1600][rows],
+            ScoringSystem::Guideline => [0, 100, 300, 500, 800][rows],
+            ScoringSystem::ClassicNes => [0, 40, 100, 300, 1200][rows],
+        };
+        points * level as i32
+    }
+}
+
+#[wasm_bindgen]
+#[repr(u8)] // 1 byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RotationSystem {
+    // clockwise in the grid of the shape, or not at all when blocked
+    Nintendo = 0,
+    // when blocked, try again one column to the right and then to the left, except for the I, as
+    // the TGM wall kicks
+    Arika = 1,
+}
+
+// the rules a board is built from, every field can be left out of the JSON
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    width: usize,
+    height: usize,
+    buffer_height: usize,
+    // leftmost column of a new shape, centered when not set
+    spawn_column: Option<usize>,
+    scoring: ScoringSystem,
+    rotation_system: RotationSystem,
+    // gravity in 1/256 rows per frame and delays in frames, see `Board::update`
+    gravity: u32,
+    are: u32,
+    line_clear_delay: u32,
+    lock_delay: u32,
+    hold_enabled: bool,
+    irs_enabled: bool,
+    ihs_enabled: bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig::guideline()
+    }
+}
+
+#[wasm_bindgen]
+impl GameConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> GameConfig {
+        GameConfig::default()
+    }
+
+    pub fn guideline() -> GameConfig {
+        GameConfig {
+            width: 10,
+            height: 20,
+            buffer_height: GUIDELINE_BUFFER_HEIGHT,
+            spawn_column: None,
+            scoring: ScoringSystem::Guideline,
+            rotation_system: RotationSystem::Nintendo,
+            gravity: gravity_for_level(1),
+            are: 0,
+            line_clear_delay: 0,
+            lock_delay: 30,
+            hold_enabled: true,
+            irs_enabled: true,
+            ihs_enabled: true,
+        }
+    }
+
+    // no hold, no lock delay, slow at level 0
+    pub fn classic_nes() -> GameConfig {
+        GameConfig {
+            width: 10,
+            height: 20,
+            buffer_height: 2,
+            spawn_column: None,
+            scoring: ScoringSystem::ClassicNes,
+            rotation_system: RotationSystem::Nintendo,
+            // the 48 frames a row of level 0 round down to 5/256 of a row a frame, a row about every
+            // 51 frames
+            gravity: GRAVITY_UNIT / 48,
+            are: 10,
+            line_clear_delay: 20,
+            lock_delay: 0,
+            hold_enabled: false,
+            irs_enabled: false,
+            ihs_enabled: false,
+        }
+    }
+
+    // the gravity and delays of the first section of master mode
+    pub fn tgm() -> GameConfig {
+        let (are, line_clear_delay, lock_delay) = master_delays(0);
+        GameConfig {
+            width: 10,
+            height: 20,
+            buffer_height: 2,
+            spawn_column: None,
+            scoring: ScoringSystem::Lines,
+            rotation_system: RotationSystem::Arika,
+            gravity: master_gravity(0),
+            are,
+            line_clear_delay,
+            lock_delay,
+            hold_enabled: false,
+            irs_enabled: true,
+            ihs_enabled: false,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("game config is always serializable")
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn set_width(&mut self, width: usize) {
+        self.width = width;
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn set_height(&mut self, height: usize) {
+        self.height = height;
+    }
+
+    pub fn get_buffer_height(&self) -> usize {
+        self.buffer_height
+    }
+
+    pub fn set_buffer_height(&mut self, buffer_height: usize) {
+        self.buffer_height = buffer_height;
+    }
+
+    pub fn get_spawn_column(&self) -> Option<usize> {
+        self.spawn_column
+    }
+
+    pub fn set_spawn_column(&mut self, spawn_column: Option<usize>) {
+        self.spawn_column = spawn_column;
+    }
+
+    pub fn get_scoring(&self) -> ScoringSystem {
+        self.scoring
+    }
+
+    pub fn set_scoring(&mut self, scoring: ScoringSystem) {
+        self.scoring = scoring;
+    }
+
+    pub fn get_rotation_system(&self) -> RotationSystem {
+        self.rotation_system
+    }
+
+    pub fn set_rotation_system(&mut self, rotation_system: RotationSystem) {
+        self.rotation_system = rotation_system;
+    }
+
+    pub fn get_gravity(&self) -> u32 {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: u32) {
+        self.gravity = gravity;
+    }
+
+    pub fn get_are(&self) -> u32 {
+        self.are
+    }
+
+    pub fn set_are(&mut self, are: u32) {
+        self.are = are;
+    }
+
+    pub fn get_line_clear_delay(&self) -> u32 {
+        self.line_clear_delay
+    }
+
+    pub fn set_line_clear_delay(&mut self, line_clear_delay: u32) {
+        self.line_clear_delay = line_clear_delay;
+    }
+
+    pub fn get_lock_delay(&self) -> u32 {
+        self.lock_delay
+    }
+
+    pub fn set_lock_delay(&mut self, lock_delay: u32) {
+        self.lock_delay = lock_delay;
+    }
+
+    pub fn is_hold_enabled(&self) -> bool {
+        self.hold_enabled
+    }
+
+    pub fn set_hold_enabled(&mut self, hold_enabled: bool) {
+        self.hold_enabled = hold_enabled;
+    }
+
+    pub fn is_irs_enabled(&self) -> bool {
+        self.irs_enabled
+    }
+
+    pub fn set_irs_enabled(&mut self, irs_enabled: bool) {
+        self.irs_enabled = irs_enabled;
+    }
+
+    pub fn is_ihs_enabled(&self) -> bool {
+        self.ihs_enabled
+    }
+
+    pub fn set_ihs_enabled(&mut self, ihs_enabled: bool) {
+        self.ihs_enabled = ihs_enabled;
+    }
+}
+
+impl GameConfig {
+    // guideline, nes or tgm
+    pub fn preset(name: &str) -> Option<GameConfig> {
+        match name {
+            "guideline" => Some(GameConfig::guideline()),
+            "nes" => Some(GameConfig::classic_nes()),
+            "tgm" => Some(GameConfig::tgm()),
+            _ => None,
+        }
+    }
+
+    pub fn try_from_json(json: &str) -> Result<GameConfig, TetrisError> {
+        serde_json::from_str(json).map_err(|_| TetrisError::InvalidConfig)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl GameConfig {
+    pub fn from_json(json: &str) -> Result<GameConfig, JsValue> {
+        GameConfig::try_from_json(json).map_err(JsValue::from)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl Board {
+    pub fn from_config(config: &GameConfig) -> Result<Board, JsValue> {
+        Board::try_from_config(config).map_err(JsValue::from)
+    }
+}
+
+impl Board {
+    pub fn try_from_config(config: &GameConfig) -> Result<Board, TetrisError> {
+        let mut board = Board::try_new(config.width, config.height, config.buffer_height)?;
+        if let Some(spawn_column) = config.spawn_column {
+            if spawn_column >= config.width {
+                return Err(TetrisError::InvalidConfig);
+            }
+        }
+        board.set_spawn_column(config.spawn_column);
+        board.set_scoring(config.scoring);
+        board.set_rotation_system(config.rotation_system);
+        board.set_gravity(config.gravity);
+        board.set_are(config.are);
+        board.set_line_clear_delay(config.line_clear_delay);
+        board.set_lock_delay(config.lock_delay);
+        board.set_hold_enabled(config.hold_enabled);
+        board.set_irs_enabled(config.irs_enabled);
+        board.set_ihs_enabled(config.ihs_enabled);
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Direction;
+
+    #[test]
+    fn test_presets() {
+        let board = Board::try_from_config(&GameConfig::new()).unwrap();
+        assert_eq!(board.get_buffer_height(), GUIDELINE_BUFFER_HEIGHT);
+        assert_eq!(board.get_scoring(), ScoringSystem::Guideline);
+        assert_eq!(board.get_lock_delay(), 30);
+        assert!(board.is_hold_enabled());
+
+        let board = Board::try_from_config(&GameConfig::classic_nes()).unwrap();
+        assert_eq!(board.get_scoring(), ScoringSystem::ClassicNes);
+        assert_eq!(board.get_gravity(), 5);
+        assert!(!board.is_hold_enabled());
+        assert!(!board.is_irs_enabled());
+
+        let board = Board::try_from_config(&GameConfig::tgm()).unwrap();
+        assert_eq!(board.get_are(), 25);
+        assert_eq!(board.get_line_clear_delay(), 40);
+        assert_eq!(board.get_lock_delay(), 30);
+        assert_eq!(board.get_rotation_system(), RotationSystem::Arika);
+        assert!(board.is_irs_enabled());
+    }
+
+    #[test]
+    fn test_json() {
+        let config = GameConfig::classic_nes();
+        assert_eq!(GameConfig::try_from_json(&config.to_json()), Ok(config));
+
+        // missing fields come from the guideline preset
+        let config = GameConfig::try_from_json(
+            r#"{"width": 8, "height": 10, "buffer_height": 0, "scoring": "ClassicNes"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.get_width(), 8);
+        assert_eq!(config.get_scoring(), ScoringSystem::ClassicNes);
+        assert_eq!(config.get_lock_delay(), 30);
+
+        assert_eq!(
+            GameConfig::try_from_json(r#"{"width": "wide"}"#),
+            Err(TetrisError::InvalidConfig)
+        );
+        let mut config = GameConfig::new();
+        config.set_width(2);
+        assert_eq!(
+            Board::try_from_config(&config).err(),
+            Some(TetrisError::WidthOutOfRange)
+        );
+        config.set_width(10);
+        config.set_spawn_column(Some(10));
+        assert_eq!(
+            Board::try_from_config(&config).err(),
+            Some(TetrisError::InvalidConfig)
+        );
+    }
+
+    #[test]
+    fn test_spawn_column() {
+        let mut config = GameConfig::new();
+        config.set_buffer_height(0);
+        config.set_spawn_column(Some(0));
+        let mut board = Board::try_from_config(&config).unwrap();
+        board.set_next_shape_type(ShapeType::Square);
+        board.tick();
+        assert_eq!(*board.get_cell(0, 0), Cell::Running);
+
+        // the shape still fits on the board
+        board.set_spawn_column(Some(9));
+        board.move_shape(Direction::Down);
+        board.tick();
+        assert_eq!(*board.get_cell(0, 8), Cell::Running);
+        assert_eq!(*board.get_cell(0, 9), Cell::Running);
+    }
+
+    #[test]
+    fn test_scoring() {
+        assert_eq!(ScoringSystem::Lines.score(2, 5, false), 2);
+        assert_eq!(ScoringSystem::Guideline.score(4, 2, false), 1600);
+        assert_eq!(ScoringSystem::Guideline.score(2, 1, true), 1200);
+        assert_eq!(ScoringSystem::Guideline.score(0, 1, true), 400);
+        assert_eq!(ScoringSystem::ClassicNes.score(1, 3, false), 120);
+
+        let mut config = GameConfig::new();
+        config.set_width(4);
+        config.set_height(6);
+        config.set_buffer_height(0);
+        let mut board = Board::try_from_config(&config).unwrap();
+        board.set_next_shape_type(ShapeType::Square);
+        for j in [0, 3].iter() {
+            board.set_cell(4, *j, Cell::Placed);
+            board.set_cell(5, *j, Cell::Placed);
+        }
+        board.tick();
+        board.move_shape(Direction::Down);
+        board.tick();
+        assert_eq!(board.get_score(), 300);
+    }
+
+    #[test]
+    fn test_hold_disabled() {
+        let mut board = Board::try_from_config(&GameConfig::classic_nes()).unwrap();
+        board.tick();
+        board.hold();
+        assert_eq!(board.get_hold_shape_type(), None);
+    }
+}
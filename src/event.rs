@@ -0,0 +1,230 @@
+use crate::action::Direction;
+use crate::tetris::*;
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+
+// events are dropped from the front when nobody polls them
+const MAX_QUEUED_EVENTS: usize = 256;
+
+#[wasm_bindgen]
+#[repr(u8)] // 1 byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    Spawn = 0,
+    Move = 1,
+    Rotate = 2,
+    Lock = 3,
+    LineClear = 4,
+    LevelUp = 5,
+    Hold = 6,
+    GameOver = 7,
+    // the goal of the game mode is reached
+    Completed = 8,
+    // the placed shape took more inputs than needed
+    FinesseFault = 9,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameEvent {
+    kind: EventKind,
+    // type of the running shape when the event happened
+    shape_type: ShapeType,
+    direction: Option<Direction>,
+    rows: Vec<u32>,
+    level: u32,
+}
+
+impl GameEvent {
+    pub(crate) fn new(kind: EventKind, shape_type: ShapeType) -> GameEvent {
+        GameEvent {
+            kind,
+            shape_type,
+            direction: None,
+            rows: vec![],
+            level: 0,
+        }
+    }
+
+    pub(crate) fn with_direction(mut self, direction: Direction) -> GameEvent {
+        self.direction = Some(direction);
+        self
+    }
+
+    pub(crate) fn with_rows(mut self, rows: &[usize]) -> GameEvent {
+        self.rows = rows.iter().map(|&row| row as u32).collect();
+        self
+    }
+
+    pub(crate) fn with_level(mut self, level: u32) -> GameEvent {
+        self.level = level;
+        self
+    }
+}
+
+#[wasm_bindgen]
+impl GameEvent {
+    pub fn get_kind(&self) -> EventKind {
+        self.kind
+    }
+
+    pub fn get_shape_type(&self) -> ShapeType {
+        self.shape_type
+    }
+
+    // only set for Move events
+    pub fn get_direction(&self) -> Option<Direction> {
+        self.direction
+    }
+
+    // cleared row indexes for LineClear events
+    pub fn get_rows(&self) -> Vec<u32> {
+        self.rows.clone()
+    }
+
+    // new level for LevelUp events
+    pub fn get_level(&self) -> u32 {
+        self.level
+    }
+}
+
+#[wasm_bindgen]
+impl Board {
+    // drain all events since the last poll, as an array of GameEvent
+    pub fn poll_events(&mut self) -> Vec<JsValue> {
+        self.take_events().into_iter().map(JsValue::from).collect()
+    }
+}
+
+impl Board {
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        self.events.drain(..).collect()
+    }
+
+    pub(crate) fn push_event(&mut self, event: GameEvent) {
+        if self.events.len() == MAX_QUEUED_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    pub(crate) fn new_event_queue() -> VecDeque<GameEvent> {
+        VecDeque::with_capacity(MAX_QUEUED_EVENTS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(events: &[GameEvent]) -> Vec<EventKind> {
+        events.iter().map(|event| event.get_kind()).collect()
+    }
+
+    #[test]
+    fn test_spawn_move_rotate_lock() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::T);
+        board.tick();
+        board.tick();
+        board.move_shape(Direction::Left);
+        board.rotate();
+        board.move_shape(Direction::Down);
+
+        let events = board.take_events();
+        assert_eq!(
+            kinds(&events),
+            vec![
+                EventKind::Spawn,
+                EventKind::Move,
+                EventKind::Rotate,
+                EventKind::Move,
+                EventKind::Lock
+            ]
+        );
+        assert_eq!(events[0].get_shape_type(), ShapeType::T);
+        assert_eq!(events[1].get_direction(), Some(Direction::Left));
+        assert_eq!(events[3].get_direction(), Some(Direction::Down));
+        assert!(board.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_blocked_move_has_no_event() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.tick();
+        for _ in 0..5 {
+            board.move_shape(Direction::Left);
+        }
+        let events = board.take_events();
+        assert_eq!(events.len(), 4); // spawn and 3 moves to reach the wall
+    }
+
+    #[test]
+    fn test_line_clear_and_level_up() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        // fill the two bottom rows but a gap for one square, on a board that is about to level up
+        for i in 8..10 {
+            for j in 0..8 {
+                if j != 3 && j != 4 {
+                    board.set_cell(i, j, Cell::Placed);
+                }
+            }
+        }
+        board.set_lines(9);
+        board.tick();
+        board.move_shape(Direction::Down);
+        board.take_events();
+        board.tick();
+
+        let events = board.take_events();
+        assert_eq!(
+            kinds(&events),
+            vec![EventKind::LineClear, EventKind::LevelUp, EventKind::Spawn]
+        );
+        assert_eq!(events[0].get_rows(), vec![8, 9]);
+        assert_eq!(events[1].get_level(), 2);
+    }
+
+    #[test]
+    fn test_hold_event() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Line);
+        board.tick();
+        board.take_events();
+        board.hold();
+        let events = board.take_events();
+        assert_eq!(kinds(&events), vec![EventKind::Hold, EventKind::Spawn]);
+        assert_eq!(events[0].get_shape_type(), ShapeType::Line);
+    }
+
+    #[test]
+    fn test_game_over_once() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Line);
+        while board.tick() {
+            board.move_shape(Direction::Down);
+        }
+        assert!(!board.tick());
+        let events = board.take_events();
+        let game_overs = events
+            .iter()
+            .filter(|event| event.get_kind() == EventKind::GameOver)
+            .count();
+        assert_eq!(game_overs, 1);
+    }
+
+    #[test]
+    fn test_queue_is_bounded() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Line);
+        board.tick();
+        for _ in 0..MAX_QUEUED_EVENTS {
+            board.rotate();
+        }
+        let events = board.take_events();
+        assert_eq!(events.len(), MAX_QUEUED_EVENTS);
+        assert_eq!(events[0].get_kind(), EventKind::Rotate);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::action::Direction;
+use crate::event::*;
+use crate::hash::StateHasher;
+use crate::input::shift;
+use crate::tetris::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+// keys a player has for a straight drop, holding a sideways key until the wall (DAS) is one input
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum FinesseKey {
+    Left,
+    Right,
+    Rotate,
+    DasLeft,
+    DasRight,
+}
+
+const FINESSE_KEYS: [FinesseKey; 5] = [
+    FinesseKey::Left,
+    FinesseKey::Right,
+    FinesseKey::Rotate,
+    FinesseKey::DasLeft,
+    FinesseKey::DasRight,
+];
+
+// fewest inputs for every straight drop of a shape, by its cells moved to the top row
+type FinesseTable = HashMap<Vec<(usize, usize)>, u32>;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Finesse {
+    // moves and rotations of the running shape since it spawned
+    piece_inputs: u32,
+    // placed shapes that took more inputs than needed
+    faults: u32,
+    // by shape type, searched on the first lock of the shape and shared with the clones of the
+    // board, they only depend on the width, the spawn column and the rotation system
+    tables: [Option<Arc<FinesseTable>>; 7],
+}
+
+impl Finesse {
+    pub(crate) fn new() -> Finesse {
+        Finesse {
+            piece_inputs: 0,
+            faults: 0,
+            tables: Default::default(),
+        }
+    }
+
+    // the spawn column or the rotation system changed
+    pub(crate) fn clear_tables(&mut self) {
+        self.tables = Default::default();
+    }
+
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.piece_inputs);
+        hasher.write_u32(self.faults);
+    }
+}
+
+#[wasm_bindgen]
+impl Board {
+    pub fn get_finesse_faults(&self) -> u32 {
+        self.finesse.faults
+    }
+
+    pub fn set_finesse_faults(&mut self, faults: u32) {
+        self.finesse.faults = faults;
+    }
+
+    // sideways moves and rotations of the running shape so far, auto repeat does not count
+    pub fn get_piece_inputs(&self) -> u32 {
+        self.finesse.piece_inputs
+    }
+}
+
+impl Board {
+    // fewest inputs to take a new shape to the cells with a straight drop, None if it cannot get
+    // there, the rows of the cells do not matter
+    pub fn minimal_inputs(&self, shape_type: ShapeType, cells: &[(usize, usize)]) -> Option<u32> {
+        let target = normalize(cells);
+        match self.finesse.tables.get(shape_type as usize)? {
+            Some(table) => table.get(&target).copied(),
+            None => self.finesse_table(shape_type)?.get(&target).copied(),
+        }
+    }
+
+    // a breadth first search of the keys on an empty board of the same width
+    fn finesse_table(&self, shape_type: ShapeType) -> Option<FinesseTable> {
+        let mut board = Board::try_new(
+            self.get_width(),
+            self.get_visible_height(),
+            self.get_buffer_height(),
+        )
+        .ok()?;
+        board.set_spawn_column(self.get_spawn_column());
+        board.set_rotation_system(self.get_rotation_system());
+        board.set_undo_enabled(false);
+        board.set_next_shape_type(shape_type);
+        board.spawn_next_shape();
+
+        let mut table = HashMap::new();
+        table.insert(normalize(board.get_running_cells()), 0);
+        let mut seen = HashSet::new();
+        seen.insert(finesse_state(&board));
+        let mut queue = VecDeque::new();
+        queue.push_back((board, 0));
+        while let Some((board, inputs)) = queue.pop_front() {
+            for &key in FINESSE_KEYS.iter() {
+                let mut next = board.clone();
+                match key {
+                    FinesseKey::Left => next.move_shape(Direction::Left),
+                    FinesseKey::Right => next.move_shape(Direction::Right),
+                    FinesseKey::Rotate => next.rotate(),
+                    FinesseKey::DasLeft => while shift(&mut next, Direction::Left) {},
+                    FinesseKey::DasRight => while shift(&mut next, Direction::Right) {},
+                }
+                if seen.insert(finesse_state(&next)) {
+                    table
+                        .entry(normalize(next.get_running_cells()))
+                        .or_insert(inputs + 1);
+                    queue.push_back((next, inputs + 1));
+                }
+            }
+        }
+        Some(table)
+    }
+
+    // called for a sideways move or a rotation of the running shape
+    pub(crate) fn record_input(&mut self) {
+        self.finesse.piece_inputs += 1;
+    }
+
+    pub(crate) fn reset_piece_inputs(&mut self) {
+        self.finesse.piece_inputs = 0;
+    }
+
+    pub(crate) fn set_piece_inputs(&mut self, piece_inputs: u32) {
+        self.finesse.piece_inputs = piece_inputs;
+    }
+
+    // run the moves without counting them as inputs, for auto repeat
+    pub(crate) fn without_inputs<T>(&mut self, f: impl FnOnce(&mut Board) -> T) -> T {
+        let piece_inputs = self.finesse.piece_inputs;
+        let result = f(self);
+        self.finesse.piece_inputs = piece_inputs;
+        result
+    }
+
+    // called when the running shape locks, before its cells are placed
+    pub(crate) fn check_finesse(&mut self) {
+        let cells = self.get_running_cells().clone();
+        // a tuck or a spin under the stack is not a straight drop
+        let covered = cells.iter().any(|&(i, j)| {
+            (0..i).any(|k| self.get_cell(k, j).is_filled() && !cells.contains(&(k, j)))
+        });
+        if covered {
+            return;
+        }
+        let shape_type = self.get_running_shape().shape_type;
+        let index = shape_type as usize;
+        if index < self.finesse.tables.len() && self.finesse.tables[index].is_none() {
+            self.finesse.tables[index] = self.finesse_table(shape_type).map(Arc::new);
+        }
+        if let Some(minimal) = self.minimal_inputs(shape_type, &cells) {
+            if self.finesse.piece_inputs > minimal {
+                self.finesse.faults += 1;
+                self.push_event(GameEvent::new(EventKind::FinesseFault, shape_type));
+            }
+        }
+    }
+}
+
+// the rotation state is part of the key, it decides how the shape rotates next
+type FinesseState = (Vec<(usize, usize)>, Vec<(i32, i32)>);
+
+fn finesse_state(board: &Board) -> FinesseState {
+    (
+        normalize(board.get_running_cells()),
+        board.get_running_shape().top_left_offset.clone(),
+    )
+}
+
+// the cells moved to the top row, sorted
+fn normalize(cells: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let top = cells.iter().map(|&(i, _)| i).min().unwrap_or(0);
+    let mut cells: Vec<_> = cells.iter().map(|&(i, j)| (i - top, j)).collect();
+    cells.sort();
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RotationSystem;
+    use crate::input::{InputController, Key};
+
+    fn board_with(shape_type: ShapeType) -> Board {
+        let mut board = Board::guideline();
+        board.set_next_shape_type(shape_type);
+        board.tick();
+        board
+    }
+
+    #[test]
+    fn test_minimal_inputs() {
+        let board = board_with(ShapeType::Square);
+        // spawned in columns 4 and 5
+        let spawn = board.get_running_cells().clone();
+        assert_eq!(board.minimal_inputs(ShapeType::Square, &spawn), Some(0));
+        assert_eq!(
+            board.minimal_inputs(ShapeType::Square, &[(38, 0), (38, 1), (39, 0), (39, 1)]),
+            Some(1)
+        );
+        assert_eq!(
+            board.minimal_inputs(ShapeType::Square, &[(38, 2), (38, 3), (39, 2), (39, 3)]),
+            Some(2)
+        );
+        // not a square
+        assert_eq!(
+            board.minimal_inputs(ShapeType::Square, &[(39, 0), (39, 1), (39, 2), (39, 3)]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_finesse_faults() {
+        let mut board = board_with(ShapeType::Square);
+        board.move_shape(Direction::Left);
+        board.move_shape(Direction::Left);
+        assert_eq!(board.get_piece_inputs(), 2);
+        board.move_shape(Direction::Down);
+        board.tick();
+        assert_eq!(board.get_finesse_faults(), 0);
+
+        // right and back left again
+        board.set_next_shape_type(ShapeType::Square);
+        board.take_events();
+        board.tick();
+        board.move_shape(Direction::Right);
+        board.move_shape(Direction::Left);
+        board.move_shape(Direction::Left);
+        board.move_shape(Direction::Down);
+        assert_eq!(board.get_finesse_faults(), 1);
+        assert!(board
+            .take_events()
+            .iter()
+            .any(|event| event.get_kind() == EventKind::FinesseFault));
+
+        // the square is searched once, the clones share its table
+        let square = ShapeType::Square as usize;
+        let table = board.finesse.tables[square].clone().unwrap();
+        let copy = board.clone();
+        assert!(Arc::ptr_eq(
+            &table,
+            copy.finesse.tables[square].as_ref().unwrap()
+        ));
+        assert!(board.finesse.tables[ShapeType::T as usize].is_none());
+        board.set_spawn_column(Some(0));
+        assert!(board.finesse.tables[square].is_none());
+        assert_eq!(
+            board.minimal_inputs(ShapeType::Square, &[(38, 0), (38, 1), (39, 0), (39, 1)]),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_rotation_system() {
+        // spawned against the right wall, the guideline L only rotates there with a kick, the I
+        // and the T need no kick to take their shortest paths
+        let mut board = Board::guideline();
+        board.set_spawn_column(Some(7));
+        let cells = [(38, 7), (38, 8), (38, 9), (39, 7)];
+        assert_eq!(board.minimal_inputs(ShapeType::MirroredL, &cells), Some(3));
+        board.set_rotation_system(RotationSystem::Arika);
+        assert_eq!(board.minimal_inputs(ShapeType::MirroredL, &cells), Some(2));
+        let line = [(39, 6), (39, 7), (39, 8), (39, 9)];
+        let minimal = board.minimal_inputs(ShapeType::Line, &line);
+        board.set_rotation_system(RotationSystem::Nintendo);
+        assert_eq!(board.minimal_inputs(ShapeType::Line, &line), minimal);
+    }
+
+    #[test]
+    fn test_das_is_one_input() {
+        let mut board = board_with(ShapeType::Square);
+        let mut input = InputController::new();
+        input.key_down(&mut board, Key::Left, 0);
+        input.update(&mut board, 1000);
+        input.key_up(&mut board, Key::Left, 1000);
+        assert_eq!(board.get_piece_inputs(), 1);
+        input.key_down(&mut board, Key::HardDrop, 1000);
+        assert_eq!(*board.get_cell(39, 0), Cell::Placed);
+        assert_eq!(board.get_finesse_faults(), 0);
+    }
+
+    #[test]
+    fn test_tucks_are_not_checked() {
+        let mut board = board_with(ShapeType::Square);
+        for j in 0..3 {
+            board.set_cell(37, j, Cell::Placed);
+        }
+        board.press(Key::SoftDrop);
+        for _ in 0..6 {
+            board.move_shape(Direction::Left);
+        }
+        board.move_shape(Direction::Down);
+        assert_eq!(*board.get_cell(39, 0), Cell::Placed);
+        assert_eq!(board.get_finesse_faults(), 0);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::hash::StateHasher;
+use crate::tetris::*;
+use rand::Rng;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[repr(u8)] // 1 byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GarbagePattern {
+    // one hole per row, always in the same column
+    SingleHole = 0,
+    // one hole per row, in a random column
+    Messy = 1,
+    // every other cell is a hole, shifted by one column on each row
+    Checker = 2,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct GarbageGenerator {
+    pattern: GarbagePattern,
+    // hole column of the single hole pattern, picked with the first row
+    well: Option<usize>,
+    rows_generated: usize,
+}
+
+impl GarbageGenerator {
+    pub(crate) fn new(pattern: GarbagePattern) -> GarbageGenerator {
+        GarbageGenerator {
+            pattern,
+            well: None,
+            rows_generated: 0,
+        }
+    }
+
+    pub(crate) fn get_pattern(&self) -> GarbagePattern {
+        self.pattern
+    }
+
+    // the next row to push at the bottom of the board, the column is drawn as a u32 so a wasm
+    // build draws the same holes as a native one
+    pub(crate) fn next_row<R: Rng + ?Sized>(&mut self, rng: &mut R, width: usize) -> Vec<Cell> {
+        let i = self.rows_generated;
+        self.rows_generated += 1;
+        match self.pattern {
+            GarbagePattern::SingleHole => {
+                let well = *self
+                    .well
+                    .get_or_insert_with(|| rng.gen_range(0, width as u32) as usize);
+                garbage_row(width, well)
+            }
+            GarbagePattern::Messy => garbage_row(width, rng.gen_range(0, width as u32) as usize),
+            GarbagePattern::Checker => (0..width)
+                .map(|j| {
+                    if (i + j).is_multiple_of(2) {
+                        Cell::Empty
+                    } else {
+                        Cell::Garbage
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u8(self.pattern as u8);
+        hasher.write_u64(self.well.map_or(u64::MAX, |well| well as u64));
+        hasher.write_usize(self.rows_generated);
+    }
+}
+
+pub(crate) fn garbage_row(width: usize, hole_column: usize) -> Vec<Cell> {
+    let mut row = vec![Cell::Garbage; width];
+    row[hole_column] = Cell::Empty;
+    row
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl Board {
+    // push the stack up with garbage rows that have a hole in the same column
+    pub fn insert_garbage(&mut self, rows: usize, hole_column: usize) -> Result<(), JsValue> {
+        self.try_insert_garbage(rows, hole_column)
+            .map_err(JsValue::from)
+    }
+}
+
+impl Board {
+    pub fn try_insert_garbage(
+        &mut self,
+        rows: usize,
+        hole_column: usize,
+    ) -> Result<(), TetrisError> {
+        let width = self.get_width();
+        if hole_column >= width {
+            return Err(TetrisError::InvalidParam);
+        }
+        let rows = rows.min(self.get_height());
+        self.push_garbage_rows(vec![garbage_row(width, hole_column); rows]);
+        Ok(())
+    }
+
+    // push the stack up with new rows at the bottom, the running shape only moves up when the
+    // stack reaches it, it tops out when filled cells go past the top or the shape has no room
+    pub(crate) fn push_garbage_rows(&mut self, rows: Vec<Vec<Cell>>) {
+        let running = self.get_running_cells().clone();
+        let mut next = self.get_cells().clone();
+        for &(i, j) in running.iter() {
+            next[i][j] = Cell::Empty;
+        }
+        let pushed_out = next
+            .drain(0..rows.len())
+            .any(|row| row.iter().any(|cell| cell.is_filled()));
+        next.extend(rows);
+
+        let top = running.iter().map(|&(i, _)| i).min().unwrap_or(0);
+        let lift = (0..=top).find(|&up| running.iter().all(|&(i, j)| !next[i - up][j].is_filled()));
+        match lift {
+            Some(up) => {
+                let lifted: Vec<(usize, usize)> =
+                    running.iter().map(|&(i, j)| (i - up, j)).collect();
+                for &(i, j) in lifted.iter() {
+                    next[i][j] = Cell::Running;
+                }
+                self.set_cells(next);
+                self.set_running_cells(lifted);
+            }
+            None => {
+                self.set_cells(next);
+                self.set_running_cells(vec![]);
+            }
+        }
+        if pushed_out || lift.is_none() {
+            self.top_out(GameOverReason::TopOut);
+        }
+    }
+
+    // number of rows that still have garbage in them
+    pub(crate) fn count_garbage_rows(&self) -> usize {
+        self.get_cells()
+            .iter()
+            .filter(|row| row.contains(&Cell::Garbage))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Direction;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn holes(row: &[Cell]) -> Vec<usize> {
+        (0..row.len()).filter(|&j| row[j] == Cell::Empty).collect()
+    }
+
+    fn generate(pattern: GarbagePattern, width: usize, count: usize) -> Vec<Vec<Cell>> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut generator = GarbageGenerator::new(pattern);
+        (0..count)
+            .map(|_| generator.next_row(&mut rng, width))
+            .collect()
+    }
+
+    #[test]
+    fn test_single_hole() {
+        let rows = generate(GarbagePattern::SingleHole, 10, 5);
+        let well = holes(&rows[0]);
+        assert_eq!(well.len(), 1);
+        assert!(rows.iter().all(|row| holes(row) == well));
+    }
+
+    #[test]
+    fn test_messy() {
+        let rows = generate(GarbagePattern::Messy, 10, 20);
+        assert!(rows.iter().all(|row| holes(row).len() == 1));
+        assert!(rows.iter().any(|row| holes(row) != holes(&rows[0])));
+    }
+
+    #[test]
+    fn test_checker() {
+        let rows = generate(GarbagePattern::Checker, 4, 3);
+        assert_eq!(holes(&rows[0]), vec![0, 2]);
+        assert_eq!(holes(&rows[1]), vec![1, 3]);
+        assert_eq!(holes(&rows[2]), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_push_garbage_rows() {
+        let mut board = Board::new(4, 6);
+        board.set_cell(5, 0, Cell::Placed);
+        board.push_garbage_rows(vec![garbage_row(4, 1), garbage_row(4, 2)]);
+        assert_eq!(*board.get_cell(3, 0), Cell::Placed);
+        assert_eq!(board.get_cells()[5], garbage_row(4, 2));
+        assert_eq!(board.get_cells()[4], garbage_row(4, 1));
+        assert_eq!(board.count_garbage_rows(), 2);
+    }
+
+    #[test]
+    fn test_push_garbage_rows_top_out() {
+        let mut board = Board::new(4, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.tick();
+        board.tick();
+        board.tick();
+        let running = board.get_running_cells().clone();
+        // the stack stays below the running shape
+        board.push_garbage_rows(vec![garbage_row(4, 0); 6]);
+        assert_eq!(*board.get_running_cells(), running);
+
+        // the stack reaches the running shape and pushes it up one row
+        board.push_garbage_rows(vec![garbage_row(4, 0)]);
+        assert!(!board.is_finished());
+        let lifted: Vec<(usize, usize)> = running.iter().map(|&(i, j)| (i - 1, j)).collect();
+        assert_eq!(*board.get_running_cells(), lifted);
+        assert!(lifted
+            .iter()
+            .all(|&(i, j)| *board.get_cell(i, j) == Cell::Running));
+
+        // filled cells pushed past the top
+        board.push_garbage_rows(vec![garbage_row(4, 0); 10]);
+        assert_eq!(board.get_game_over_reason(), Some(GameOverReason::TopOut));
+    }
+
+    #[test]
+    fn test_insert_garbage() {
+        let mut board = Board::guideline();
+        board.set_next_shape_type(ShapeType::Square);
+        board.tick();
+        let running = board.get_running_cells().clone();
+        assert_eq!(
+            board.try_insert_garbage(2, 10),
+            Err(TetrisError::InvalidParam)
+        );
+        // the running shape is far above the stack and stays where it is
+        board.try_insert_garbage(2, 3).unwrap();
+        assert_eq!(*board.get_running_cells(), running);
+        assert_eq!(board.get_cells()[39], garbage_row(10, 3));
+        assert_eq!(board.get_cells()[38], garbage_row(10, 3));
+        assert_eq!(board.count_garbage_rows(), 2);
+
+        // the stack reaches the running shape and pushes it up
+        let lowest = running.iter().map(|&(i, _)| i).max().unwrap();
+        board.try_insert_garbage(40 - lowest - 2, 3).unwrap();
+        assert!(!board.is_finished());
+        for &(i, j) in board.get_running_cells().iter() {
+            assert!(i < lowest);
+            assert_eq!(*board.get_cell(i, j), Cell::Running);
+        }
+        board.move_shape(Direction::Down);
+        assert!(board.get_cells()[lowest - 1].contains(&Cell::Placed));
+
+        // filled cells pushed past the top
+        board.try_insert_garbage(40, 0).unwrap();
+        assert_eq!(board.get_game_over_reason(), Some(GameOverReason::TopOut));
+    }
+}
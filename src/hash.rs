@@ -0,0 +1,334 @@
+// a stable hash of the whole game state, the same on every run and every platform, so replays
+// and network peers can find out when they stop playing the same game
+use crate::config::GameConfig;
+use crate::input::InputController;
+use crate::net::{KeyEvent, FRAME_MS};
+use crate::tetris::*;
+use crate::versus::Match;
+use rand::{rngs::StdRng, Error, Rng, RngCore, SeedableRng};
+use wasm_bindgen::prelude::*;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// 64-bit FNV-1a, integers are written in little endian with a fixed width and usize as u64, so
+// a wasm build hashes like a native one
+#[derive(Debug, Clone)]
+pub struct StateHasher {
+    hash: u64,
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        StateHasher::new()
+    }
+}
+
+impl StateHasher {
+    pub fn new() -> StateHasher {
+        StateHasher {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash ^= u64::from(byte);
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.write(&[value]);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    // None and Some(0) hash differently
+    pub fn write_option_u8(&mut self, value: Option<u8>) {
+        match value {
+            Some(value) => self.write(&[1, value]),
+            None => self.write_u8(0),
+        }
+    }
+
+    pub fn write_positions(&mut self, positions: &[(usize, usize)]) {
+        self.write_usize(positions.len());
+        for &(i, j) in positions {
+            self.write_usize(i);
+            self.write_usize(j);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+// a StdRng that counts its draws, the state of a StdRng is hidden but the seed and the number of
+// draws decide it as well
+#[derive(Debug, Clone)]
+pub(crate) struct SeededRng {
+    seed: u64,
+    draws: u64,
+    rng: StdRng,
+}
+
+impl SeededRng {
+    pub(crate) fn seed_from_u64(seed: u64) -> SeededRng {
+        SeededRng {
+            seed,
+            draws: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub(crate) fn from_entropy() -> SeededRng {
+        SeededRng::seed_from_u64(StdRng::from_entropy().gen())
+    }
+
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u64(self.seed);
+        hasher.write_u64(self.draws);
+    }
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        self.draws += 1;
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.draws += 1;
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.draws += 1;
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.draws += 1;
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+#[wasm_bindgen]
+impl Board {
+    // the state hash in hex, a JS number cannot hold all of it
+    pub fn get_state_hash(&self) -> String {
+        format!("{:016x}", self.state_hash())
+    }
+}
+
+impl Board {
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        self.hash_state(&mut hasher);
+        hasher.finish()
+    }
+
+    // everything that changes how the game goes on, the undo history and the events waiting to
+    // be taken are left out
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.get_width());
+        hasher.write_usize(self.get_height());
+        hasher.write_usize(self.get_buffer_height());
+        for row in self.get_cells() {
+            for &cell in row {
+                hasher.write_u8(cell as u8);
+            }
+        }
+        hasher.write_u64(
+            self.get_spawn_column()
+                .map_or(u64::MAX, |column| column as u64),
+        );
+        hasher.write_bool(self.is_hold_enabled());
+        hasher.write_u8(self.get_rotation_system() as u8);
+
+        let shape = self.get_running_shape();
+        hasher.write_u8(shape.shape_type as u8);
+        hasher.write_positions(&shape.running_cells);
+        hasher.write_usize(shape.top_left_offset.len());
+        for &(i, j) in shape.top_left_offset.iter() {
+            hasher.write_u32(i as u32);
+            hasher.write_u32(j as u32);
+        }
+        hasher.write_u8(self.get_next_shape_type() as u8);
+        hasher.write_option_u8(
+            self.get_hold_shape_type()
+                .map(|shape_type| shape_type as u8),
+        );
+        hasher.write_bool(self.is_hold_used());
+
+        hasher.write_u32(self.get_score() as u32);
+        hasher.write_u32(self.get_lines());
+        hasher.write_u32(self.get_pieces_placed());
+        for &count in self.get_piece_counts() {
+            hasher.write_u32(count);
+        }
+        hasher.write_u32(self.get_garbage_cleared());
+        hasher.write_u32(self.get_elapsed_ms());
+        hasher.write_bool(self.is_last_move_rotation());
+        hasher.write_bool(self.is_t_spin());
+        hasher.write_option_u8(self.get_game_over_reason().map(|reason| reason as u8));
+
+        self.timing.hash_state(hasher);
+        self.mode.hash_state(hasher);
+        self.finesse.hash_state(hasher);
+        self.versus.hash_state(hasher);
+        match &self.puzzle {
+            Some(puzzle) => {
+                hasher.write_u8(1);
+                puzzle.hash_state(hasher);
+            }
+            None => hasher.write_u8(0),
+        }
+        self.rng.hash_state(hasher);
+    }
+}
+
+#[wasm_bindgen]
+impl Match {
+    pub fn get_state_hash(&self) -> String {
+        format!("{:016x}", self.state_hash())
+    }
+}
+
+impl Match {
+    // all the boards and the keys held on them
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        self.hash_state(&mut hasher);
+        hasher.finish()
+    }
+}
+
+// play the key events of a log on a seeded board frame by frame, like the lockstep clients do,
+// and hash the state after every frame
+pub fn replay(
+    config: &GameConfig,
+    seed: u32,
+    log: &[(u32, KeyEvent)],
+    frames: u32,
+) -> Result<Vec<u64>, TetrisError> {
+    let mut board = Board::try_from_config(config)?;
+    board.set_seed(seed);
+    let mut input = InputController::new();
+    let mut hashes = vec![];
+    for frame in 0..frames {
+        let time_ms = frame * FRAME_MS;
+        for (_, event) in log.iter().filter(|(at, _)| *at == frame) {
+            if event.pressed {
+                input.key_down(&mut board, event.key, time_ms);
+            } else {
+                input.key_up(&mut board, event.key, time_ms);
+            }
+        }
+        input.update(&mut board, time_ms);
+        board.update(FRAME_MS);
+        hashes.push(board.state_hash());
+    }
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Key;
+
+    #[test]
+    fn test_hasher() {
+        // test vectors of 64-bit FNV-1a
+        let mut hasher = StateHasher::new();
+        assert_eq!(hasher.finish(), 0xcbf2_9ce4_8422_2325);
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+        let mut hasher = StateHasher::new();
+        hasher.write(b"foobar");
+        assert_eq!(hasher.finish(), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let mut board = Board::guideline();
+        board.set_seed(1);
+        let mut other = board.clone();
+        assert_eq!(board.state_hash(), other.state_hash());
+
+        // the same moves on the same seed
+        board.tick();
+        other.tick();
+        assert_eq!(board.state_hash(), other.state_hash());
+        board.rotate();
+        assert_ne!(board.state_hash(), other.state_hash());
+        other.rotate();
+        assert_eq!(board.state_hash(), other.state_hash());
+
+        // a generator that has drawn one more number
+        let mut reseeded = Board::guideline();
+        reseeded.set_seed(2);
+        let mut board = Board::guideline();
+        board.set_seed(1);
+        assert_ne!(board.state_hash(), reseeded.state_hash());
+        let before = board.state_hash();
+        let _: u32 = board.rng.gen();
+        assert_ne!(board.state_hash(), before);
+        let mut other = Board::guideline();
+        other.set_seed(1);
+        let _: u32 = other.rng.gen();
+        assert_eq!(board.state_hash(), other.state_hash());
+        let _: u32 = board.rng.gen();
+        assert_ne!(board.state_hash(), before);
+
+        assert_eq!(board.get_state_hash().len(), 16);
+    }
+
+    #[test]
+    fn test_replay() {
+        let config = GameConfig::guideline();
+        let press = |key| KeyEvent { key, pressed: true };
+        let release = |key| KeyEvent {
+            key,
+            pressed: false,
+        };
+        let log = vec![
+            (10, press(Key::Left)),
+            (40, release(Key::Left)),
+            (50, press(Key::HardDrop)),
+            (51, release(Key::HardDrop)),
+            (80, press(Key::Rotate)),
+            (81, release(Key::Rotate)),
+            (90, press(Key::HardDrop)),
+        ];
+        let hashes = replay(&config, 7, &log, 120).unwrap();
+        assert_eq!(hashes.len(), 120);
+        assert_eq!(replay(&config, 7, &log, 120).unwrap(), hashes);
+        // the state changes as the game goes on
+        assert_ne!(hashes[0], hashes[119]);
+
+        // another seed, or a key one frame late, is another game
+        assert_ne!(replay(&config, 8, &log, 120).unwrap(), hashes);
+        let mut late = log.clone();
+        late[2].0 += 1;
+        let late = replay(&config, 7, &late, 120).unwrap();
+        assert_eq!(late[..50], hashes[..50]);
+        assert_ne!(late[50], hashes[50]);
+    }
+}
@@ -0,0 +1,406 @@
+use crate::hash::SeededRng;
+use crate::mode::ModeState;
+use crate::puzzle::Puzzle;
+use crate::tetris::*;
+use crate::timing::Timing;
+use crate::versus::VersusState;
+use wasm_bindgen::prelude::*;
+
+const DEFAULT_UNDO_DEPTH: usize = 100;
+
+// state of the board when a shape has just been spawned
+#[derive(Debug, Clone)]
+pub(crate) struct Snapshot {
+    cells: Vec<Vec<Cell>>,
+    running_shape: Shape,
+    next_shape_type: ShapeType,
+    hold_shape_type: Option<ShapeType>,
+    hold_used: bool,
+    score: i32,
+    lines: u32,
+    pieces_placed: u32,
+    piece_counts: [u32; 7],
+    finesse_faults: u32,
+    piece_inputs: u32,
+    garbage_cleared: u32,
+    elapsed_ms: u32,
+    last_move_rotation: bool,
+    t_spin: bool,
+    puzzle: Option<Puzzle>,
+    mode: ModeState,
+    timing: Timing,
+    versus: VersusState,
+    // the same shapes come again after an undo
+    rng: SeededRng,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct History {
+    enabled: bool,
+    depth: usize,
+    // snapshot of the running shape at spawn, pushed to the undo stack once it locks
+    spawn: Option<Snapshot>,
+    undo_stack: Vec<Snapshot>,
+    // the state to go back to, and the spawn snapshot of its running shape
+    redo_stack: Vec<(Snapshot, Option<Snapshot>)>,
+}
+
+impl History {
+    pub(crate) fn new() -> History {
+        History {
+            enabled: true,
+            depth: DEFAULT_UNDO_DEPTH,
+            spawn: None,
+            undo_stack: vec![],
+            redo_stack: vec![],
+        }
+    }
+
+    pub(crate) fn disabled(depth: usize) -> History {
+        History {
+            enabled: false,
+            depth,
+            spawn: None,
+            undo_stack: vec![],
+            redo_stack: vec![],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+}
+
+#[wasm_bindgen]
+impl Board {
+    // go back to the moment the last placed shape spawned, return false if nothing to undo
+    pub fn undo(&mut self) -> bool {
+        if !self.history.enabled {
+            return false;
+        }
+        match self.history.undo_stack.pop() {
+            Some(target) => {
+                let current = self.snapshot();
+                let current_spawn = self.history.spawn.take();
+                self.history.redo_stack.push((current, current_spawn));
+                self.restore(&target);
+                self.history.spawn = Some(target);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // re-apply the last undone placement, return false if nothing to redo
+    pub fn redo(&mut self) -> bool {
+        if !self.history.enabled {
+            return false;
+        }
+        match self.history.redo_stack.pop() {
+            Some((target, target_spawn)) => {
+                if let Some(spawn) = self.history.spawn.take() {
+                    self.history.undo_stack.push(spawn);
+                }
+                self.restore(&target);
+                self.history.spawn = target_spawn;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.enabled && !self.history.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.enabled && !self.history.redo_stack.is_empty()
+    }
+
+    pub fn get_undo_depth(&self) -> usize {
+        self.history.depth
+    }
+
+    pub fn set_undo_depth(&mut self, depth: usize) {
+        self.history.depth = depth;
+        let len = self.history.undo_stack.len();
+        if len > depth {
+            self.history.undo_stack.drain(0..len - depth);
+        }
+    }
+
+    pub fn is_undo_enabled(&self) -> bool {
+        self.history.enabled
+    }
+
+    // ranked modes should disable undo, which also drops everything recorded so far
+    pub fn set_undo_enabled(&mut self, enabled: bool) {
+        self.history.enabled = enabled;
+        if !enabled {
+            self.history.clear();
+        }
+    }
+}
+
+impl Board {
+    pub(crate) fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            cells: self.get_cells().clone(),
+            running_shape: self.get_running_shape().clone(),
+            next_shape_type: self.get_next_shape_type(),
+            hold_shape_type: self.get_hold_shape_type(),
+            hold_used: self.is_hold_used(),
+            score: self.get_score(),
+            lines: self.get_lines(),
+            pieces_placed: self.get_pieces_placed(),
+            piece_counts: *self.get_piece_counts(),
+            finesse_faults: self.get_finesse_faults(),
+            piece_inputs: self.get_piece_inputs(),
+            garbage_cleared: self.get_garbage_cleared(),
+            elapsed_ms: self.get_elapsed_ms(),
+            last_move_rotation: self.is_last_move_rotation(),
+            t_spin: self.is_t_spin(),
+            puzzle: self.puzzle.clone(),
+            mode: self.mode.clone(),
+            timing: self.timing.clone(),
+            versus: self.versus.clone(),
+            rng: self.rng.clone(),
+        }
+    }
+
+    pub(crate) fn restore(&mut self, snapshot: &Snapshot) {
+        self.set_cells(snapshot.cells.clone());
+        self.set_running_shape(snapshot.running_shape.clone());
+        self.set_next_shape_type(snapshot.next_shape_type);
+        self.set_hold_shape_type(snapshot.hold_shape_type);
+        self.set_hold_used(snapshot.hold_used);
+        self.set_score(snapshot.score);
+        self.set_lines(snapshot.lines);
+        self.set_pieces_placed(snapshot.pieces_placed);
+        self.set_piece_counts(snapshot.piece_counts);
+        self.set_finesse_faults(snapshot.finesse_faults);
+        self.set_piece_inputs(snapshot.piece_inputs);
+        self.set_garbage_cleared(snapshot.garbage_cleared);
+        self.set_elapsed_ms(snapshot.elapsed_ms);
+        self.set_last_move_rotation(snapshot.last_move_rotation);
+        self.set_t_spin(snapshot.t_spin);
+        self.puzzle = snapshot.puzzle.clone();
+        self.mode = snapshot.mode.clone();
+        // soft drop follows the key, which may have been released since
+        let soft_drop_factor = self.get_soft_drop_factor();
+        self.timing = snapshot.timing.clone();
+        self.set_soft_drop_factor(soft_drop_factor);
+        self.versus = snapshot.versus.clone();
+        self.rng = snapshot.rng.clone();
+        // undoing the placement that topped out resumes the game
+        self.set_game_over_reason(None);
+    }
+
+    // forget everything recorded so far, when a new game is loaded on the board
+    pub(crate) fn clear_history(&mut self) {
+        self.history.spawn = None;
+        self.history.clear();
+    }
+
+    // called once a shape is added to the board
+    pub(crate) fn record_spawn(&mut self) {
+        if self.history.enabled {
+            self.history.spawn = Some(self.snapshot());
+        }
+    }
+
+    // called when the running shape is locked
+    pub(crate) fn record_lock(&mut self) {
+        if !self.history.enabled {
+            return;
+        }
+        if let Some(spawn) = self.history.spawn.take() {
+            self.history.undo_stack.push(spawn);
+            self.set_undo_depth(self.history.depth);
+        }
+        self.history.redo_stack.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Direction;
+    use crate::garbage::GarbagePattern;
+    use crate::mode::{GameMode, GameStatus};
+
+    fn board_with_square() -> Board {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.tick();
+        board
+    }
+
+    #[test]
+    fn test_undo_placement() {
+        let mut board = board_with_square();
+        assert!(!board.can_undo());
+
+        board.move_shape(Direction::Left);
+        board.move_shape(Direction::Down);
+        assert_eq!(*board.get_cell(9, 2), Cell::Placed);
+        assert!(board.can_undo());
+
+        assert!(board.undo());
+        assert_eq!(*board.get_cell(9, 2), Cell::Empty);
+        assert_eq!(*board.get_cell(0, 3), Cell::Running);
+        assert_eq!(*board.get_cell(1, 4), Cell::Running);
+        assert!(!board.undo());
+
+        // the restored shape can be placed somewhere else
+        board.move_shape(Direction::Right);
+        board.move_shape(Direction::Down);
+        assert_eq!(*board.get_cell(9, 5), Cell::Placed);
+        assert_eq!(*board.get_cell(9, 2), Cell::Empty);
+    }
+
+    #[test]
+    fn test_redo_placement() {
+        let mut board = board_with_square();
+        board.move_shape(Direction::Down);
+        board.tick();
+        let placed = board.get_cells().clone();
+
+        assert!(board.undo());
+        assert!(board.can_redo());
+        assert!(board.redo());
+        assert_eq!(*board.get_cells(), placed);
+        assert!(!board.redo());
+
+        // undo again after redo goes back to the same spawn
+        assert!(board.undo());
+        assert_eq!(*board.get_cell(0, 3), Cell::Running);
+        assert_eq!(*board.get_cell(9, 3), Cell::Empty);
+    }
+
+    #[test]
+    fn test_new_lock_clears_redo() {
+        let mut board = board_with_square();
+        board.move_shape(Direction::Down);
+        board.undo();
+        board.move_shape(Direction::Left);
+        board.move_shape(Direction::Down);
+        assert!(!board.can_redo());
+    }
+
+    #[test]
+    fn test_undo_top_out() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Line);
+        while board.tick() {
+            board.move_shape(Direction::Down);
+        }
+        assert!(board.undo());
+        assert!(!board.is_game_over());
+        board.move_shape(Direction::Left);
+        board.move_shape(Direction::Down);
+        assert!(board.tick());
+    }
+
+    #[test]
+    fn test_undo_depth() {
+        let mut board = board_with_square();
+        board.set_undo_depth(2);
+        for _ in 0..3 {
+            board.move_shape(Direction::Down);
+            board.tick();
+        }
+        assert!(board.undo());
+        assert!(board.undo());
+        assert!(!board.undo());
+    }
+
+    #[test]
+    fn test_undo_disabled() {
+        let mut board = board_with_square();
+        board.move_shape(Direction::Down);
+        board.set_undo_enabled(false);
+        assert!(!board.can_undo());
+        assert!(!board.undo());
+
+        board.tick();
+        board.move_shape(Direction::Down);
+        assert!(!board.undo());
+    }
+
+    #[test]
+    fn test_clone_without_history() {
+        let mut board = board_with_square();
+        board.move_shape(Direction::Down);
+        board.tick();
+        let copy = board.clone_without_history();
+        assert!(!copy.is_undo_enabled());
+        assert!(!copy.can_undo());
+        assert_eq!(copy.get_cells(), board.get_cells());
+        assert_eq!(copy.state_hash(), board.state_hash());
+        assert!(board.can_undo());
+    }
+
+    #[test]
+    fn test_undo_dig() {
+        let mut board = Board::new(8, 10);
+        board.set_seed(1);
+        board.set_mode(GameMode::Dig);
+        board.set_garbage_goal(2);
+        board.set_garbage_pattern(GarbagePattern::SingleHole);
+        board.set_next_shape_type(ShapeType::Line);
+        board.tick();
+        let spawned = board.state_hash();
+
+        // fill the well of the two garbage rows
+        let well = (0..8).find(|&j| *board.get_cell(9, j) == Cell::Empty);
+        for _ in 0..8 {
+            board.move_shape(Direction::Left);
+        }
+        for _ in 0..well.unwrap() {
+            board.move_shape(Direction::Right);
+        }
+        board.move_shape(Direction::Down);
+        board.tick();
+        assert_eq!(board.get_status(), GameStatus::Completed);
+        assert_eq!(board.get_garbage_cleared(), 2);
+
+        // the cleared garbage is not counted twice, the same shapes and holes come again
+        assert!(board.undo());
+        assert_eq!(board.get_status(), GameStatus::Playing);
+        assert_eq!(board.get_garbage_cleared(), 0);
+        assert_eq!(board.get_garbage_left(), 2);
+        assert_eq!(board.count_garbage_rows(), 2);
+        assert_eq!(board.state_hash(), spawned);
+    }
+
+    #[test]
+    fn test_undo_sprint() {
+        let mut board = Board::new(8, 10);
+        board.set_mode(GameMode::Sprint);
+        // ranked modes turn undo off
+        board.set_undo_enabled(true);
+        board.set_line_goal(2);
+        for i in 8..10 {
+            for j in 2..8 {
+                board.set_cell(i, j, Cell::Placed);
+            }
+        }
+        board.set_next_shape_type(ShapeType::Square);
+        board.tick();
+        board.update(500);
+        for _ in 0..4 {
+            board.move_shape(Direction::Left);
+        }
+        board.move_shape(Direction::Down);
+        board.tick();
+        assert_eq!(board.get_status(), GameStatus::Completed);
+        assert_eq!(board.get_lines(), 2);
+
+        assert!(board.undo());
+        assert_eq!(board.get_status(), GameStatus::Playing);
+        assert_eq!(board.get_lines(), 0);
+        assert_eq!(board.get_elapsed_ms(), 0);
+    }
+}
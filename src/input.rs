@@ -0,0 +1,322 @@
+use crate::action::Direction;
+use crate::hash::StateHasher;
+use crate::tetris::*;
+use wasm_bindgen::prelude::*;
+
+// 10 and 2 frames at 60 frames per second
+pub const DEFAULT_DAS_MS: u32 = 167;
+pub const DEFAULT_ARR_MS: u32 = 33;
+pub const DEFAULT_DCD_MS: u32 = 0;
+pub const DEFAULT_SOFT_DROP_FACTOR: u32 = 20;
+
+#[wasm_bindgen]
+#[repr(u8)] // 1 byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    Left = 0,
+    Right = 1,
+    SoftDrop = 2,
+    HardDrop = 3,
+    Rotate = 4,
+    Hold = 5,
+}
+
+// turns key down and up events into moves on a board, so the handling does not depend on the
+// key repeat rate of the machine
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct InputController {
+    // delayed auto shift, how long a sideways key is held before it repeats
+    das_ms: u32,
+    // auto repeat rate, 0 shifts to the wall at once
+    arr_ms: u32,
+    // DAS cut delay, the repeat waits this long after a rotation, hold or hard drop
+    dcd_ms: u32,
+    soft_drop_factor: u32,
+    left_down: bool,
+    right_down: bool,
+    // the sideways key pressed last wins while both are held
+    direction: Option<Direction>,
+    // when the DAS of the direction started charging
+    das_start_ms: u32,
+    repeats: u32,
+}
+
+impl Default for InputController {
+    fn default() -> Self {
+        InputController::new()
+    }
+}
+
+#[wasm_bindgen]
+impl InputController {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> InputController {
+        InputController {
+            das_ms: DEFAULT_DAS_MS,
+            arr_ms: DEFAULT_ARR_MS,
+            dcd_ms: DEFAULT_DCD_MS,
+            soft_drop_factor: DEFAULT_SOFT_DROP_FACTOR,
+            left_down: false,
+            right_down: false,
+            direction: None,
+            das_start_ms: 0,
+            repeats: 0,
+        }
+    }
+
+    pub fn get_das_ms(&self) -> u32 {
+        self.das_ms
+    }
+
+    pub fn set_das_ms(&mut self, das_ms: u32) {
+        self.das_ms = das_ms;
+    }
+
+    pub fn get_arr_ms(&self) -> u32 {
+        self.arr_ms
+    }
+
+    pub fn set_arr_ms(&mut self, arr_ms: u32) {
+        self.arr_ms = arr_ms;
+    }
+
+    pub fn get_dcd_ms(&self) -> u32 {
+        self.dcd_ms
+    }
+
+    pub fn set_dcd_ms(&mut self, dcd_ms: u32) {
+        self.dcd_ms = dcd_ms;
+    }
+
+    pub fn get_soft_drop_factor(&self) -> u32 {
+        self.soft_drop_factor
+    }
+
+    pub fn set_soft_drop_factor(&mut self, soft_drop_factor: u32) {
+        self.soft_drop_factor = soft_drop_factor;
+    }
+
+    // the browser key repeat should be filtered out before calling this
+    pub fn key_down(&mut self, board: &mut Board, key: Key, time_ms: u32) {
+        match key {
+            Key::Left | Key::Right => {
+                let direction = if key == Key::Left {
+                    self.left_down = true;
+                    Direction::Left
+                } else {
+                    self.right_down = true;
+                    Direction::Right
+                };
+                board.move_shape(direction);
+                self.start_das(direction, time_ms);
+            }
+            Key::SoftDrop => board.set_soft_drop_factor(self.soft_drop_factor.max(1)),
+            Key::HardDrop => {
+                board.move_shape(Direction::Down);
+                self.cut_das(time_ms);
+            }
+            Key::Rotate => {
+                board.rotate();
+                self.cut_das(time_ms);
+            }
+            Key::Hold => {
+                board.hold();
+                self.cut_das(time_ms);
+            }
+        }
+    }
+
+    pub fn key_up(&mut self, board: &mut Board, key: Key, time_ms: u32) {
+        match key {
+            Key::Left => self.left_down = false,
+            Key::Right => self.right_down = false,
+            Key::SoftDrop => board.set_soft_drop_factor(0),
+            _ => return,
+        }
+        // fall back to the other sideways key if it is still held
+        self.direction = match (self.left_down, self.right_down, self.direction) {
+            (true, true, direction) => direction,
+            (true, false, Some(Direction::Left)) => Some(Direction::Left),
+            (true, false, _) => {
+                self.start_das(Direction::Left, time_ms);
+                Some(Direction::Left)
+            }
+            (false, true, Some(Direction::Right)) => Some(Direction::Right),
+            (false, true, _) => {
+                self.start_das(Direction::Right, time_ms);
+                Some(Direction::Right)
+            }
+            (false, false, _) => None,
+        };
+    }
+
+    // auto repeat the held sideways key, called every frame before `Board::update`
+    pub fn update(&mut self, board: &mut Board, time_ms: u32) {
+        let direction = match self.direction {
+            Some(direction) => direction,
+            None => return,
+        };
+        let charged_ms = self.das_start_ms.saturating_add(self.das_ms);
+        if time_ms < charged_ms {
+            return;
+        }
+        // the repeats are part of the key press, not new inputs
+        board.without_inputs(|board| {
+            if self.arr_ms == 0 {
+                while shift(board, direction) {}
+                return;
+            }
+            let due = (time_ms - charged_ms) / self.arr_ms + 1;
+            while self.repeats < due {
+                self.repeats += 1;
+                if !shift(board, direction) {
+                    // keep the DAS charged against the wall
+                    self.repeats = due;
+                }
+            }
+        });
+    }
+}
+
+impl InputController {
+    fn start_das(&mut self, direction: Direction, time_ms: u32) {
+        self.direction = Some(direction);
+        self.das_start_ms = time_ms;
+        self.repeats = 0;
+    }
+
+    // a charged DAS waits for the DAS cut delay before it repeats again
+    fn cut_das(&mut self, time_ms: u32) {
+        if self.direction.is_none() || self.dcd_ms == 0 {
+            return;
+        }
+        let charged_ms = self.das_start_ms.saturating_add(self.das_ms);
+        if time_ms >= charged_ms {
+            self.das_start_ms = time_ms
+                .saturating_add(self.dcd_ms)
+                .saturating_sub(self.das_ms);
+            self.repeats = 0;
+        }
+    }
+
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.das_ms);
+        hasher.write_u32(self.arr_ms);
+        hasher.write_u32(self.dcd_ms);
+        hasher.write_u32(self.soft_drop_factor);
+        hasher.write_bool(self.left_down);
+        hasher.write_bool(self.right_down);
+        hasher.write_option_u8(self.direction.map(|direction| direction as u8));
+        hasher.write_u32(self.das_start_ms);
+        hasher.write_u32(self.repeats);
+    }
+}
+
+// return false if the shape did not move
+pub(crate) fn shift(board: &mut Board, direction: Direction) -> bool {
+    let before = board.get_running_cells().clone();
+    board.move_shape(direction);
+    !before.is_empty() && *board.get_running_cells() != before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_with_square() -> Board {
+        let mut board = Board::new(10, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.tick();
+        board
+    }
+
+    fn left_column(board: &Board) -> usize {
+        board
+            .get_running_cells()
+            .iter()
+            .map(|&(_, j)| j)
+            .min()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_das_and_arr() {
+        let mut board = board_with_square();
+        let mut input = InputController::new();
+        input.set_das_ms(100);
+        input.set_arr_ms(20);
+        input.key_down(&mut board, Key::Left, 1000);
+        assert_eq!(left_column(&board), 3);
+        input.update(&mut board, 1099);
+        assert_eq!(left_column(&board), 3);
+        input.update(&mut board, 1100);
+        assert_eq!(left_column(&board), 2);
+        input.update(&mut board, 1145);
+        assert_eq!(left_column(&board), 0);
+
+        // the other key takes over, with its own DAS
+        input.key_down(&mut board, Key::Right, 1150);
+        assert_eq!(left_column(&board), 1);
+        input.update(&mut board, 1200);
+        assert_eq!(left_column(&board), 1);
+        input.key_up(&mut board, Key::Right, 1210);
+        input.update(&mut board, 1309);
+        assert_eq!(left_column(&board), 1);
+        input.update(&mut board, 1310);
+        assert_eq!(left_column(&board), 0);
+    }
+
+    #[test]
+    fn test_instant_arr() {
+        let mut board = board_with_square();
+        let mut input = InputController::new();
+        input.set_arr_ms(0);
+        input.key_down(&mut board, Key::Right, 0);
+        input.update(&mut board, DEFAULT_DAS_MS);
+        assert_eq!(left_column(&board), 8);
+
+        input.key_up(&mut board, Key::Right, DEFAULT_DAS_MS);
+        input.update(&mut board, 1000);
+        assert_eq!(input.direction, None);
+    }
+
+    #[test]
+    fn test_das_cut_delay() {
+        let mut board = board_with_square();
+        let mut input = InputController::new();
+        input.set_das_ms(100);
+        input.set_arr_ms(50);
+        input.set_dcd_ms(30);
+        input.key_down(&mut board, Key::Left, 0);
+        input.update(&mut board, 100);
+        assert_eq!(left_column(&board), 2);
+        input.key_down(&mut board, Key::Rotate, 110);
+        input.update(&mut board, 139);
+        assert_eq!(left_column(&board), 2);
+        input.update(&mut board, 140);
+        assert_eq!(left_column(&board), 1);
+
+        // a huge delay holds the repeats off instead of overflowing
+        input.set_dcd_ms(u32::MAX);
+        input.key_down(&mut board, Key::Rotate, 200);
+        input.update(&mut board, 10_000);
+        assert_eq!(left_column(&board), 1);
+    }
+
+    #[test]
+    fn test_soft_drop() {
+        let mut board = board_with_square();
+        let mut input = InputController::new();
+        input.set_soft_drop_factor(32);
+        input.key_down(&mut board, Key::SoftDrop, 0);
+        assert_eq!(board.get_soft_drop_factor(), 32);
+        // 8 * 32 / 256 is one row a frame, and the shape does not lock at once
+        board.update(50);
+        assert_eq!(*board.get_cell(3, 4), Cell::Running);
+        input.key_up(&mut board, Key::SoftDrop, 50);
+        assert_eq!(board.get_soft_drop_factor(), 0);
+        board.update(50);
+        assert_eq!(*board.get_cell(3, 4), Cell::Running);
+    }
+}
@@ -1,8 +1,33 @@
 use wasm_bindgen::prelude::*;
 //use web_sys::console;
 
+// the public modules are the ones the tools in src/bin and the tests in tests/ use, the rest is
+// only exported to JavaScript
 mod action;
-mod tetris;
+pub mod ai;
+// helpers of the command line tools
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+pub mod config;
+mod event;
+mod finesse;
+mod garbage;
+pub mod hash;
+mod history;
+pub mod input;
+mod mode;
+pub mod net;
+mod puzzle;
+// the relay server only runs natively
+#[cfg(not(target_arch = "wasm32"))]
+pub mod relay;
+mod search;
+pub mod sim;
+pub mod tbp;
+pub mod tetris;
+mod timing;
+pub mod tune;
+mod versus;
 
 // When the `wee_alloc` feature is enabled, this uses `wee_alloc` as the global
 // allocator.
@@ -14,6 +39,8 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 #[wasm_bindgen]
 pub fn init_panic_hook() {
+    // the hook is only a dependency in debug mode
+    #[cfg(debug_assertions)]
     console_error_panic_hook::set_once();
 }
 
@@ -0,0 +1,850 @@
+use crate::event::*;
+use crate::garbage::*;
+use crate::hash::{SeededRng, StateHasher};
+use crate::tetris::*;
+use crate::timing::*;
+use wasm_bindgen::prelude::*;
+
+pub const SPRINT_LINE_GOAL: u32 = 40;
+pub const ULTRA_TIME_LIMIT_MS: u32 = 120_000;
+pub const DIG_GARBAGE_GOAL: u32 = 18;
+pub const DIG_GARBAGE_HEIGHT: usize = 10;
+pub const MARATHON_LINE_GOAL: u32 = 150;
+pub const MARATHON_MAX_LEVEL: u32 = 15;
+pub const MASTER_MAX_LEVEL: u32 = 999;
+
+// mixed into the seed of the dig garbage
+const DIG_SEED: u64 = 0xd1b5_4a32_d192_ed03;
+
+// master gravity by internal level, in 1/256 rows per frame
+const MASTER_GRAVITY: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, INSTANT_GRAVITY),
+];
+
+// master delays by internal level, in frames: ARE, line clear delay and lock delay
+const MASTER_DELAYS: [(u32, u32, u32, u32); 6] = [
+    (0, 25, 40, 30),
+    (500, 25, 25, 30),
+    (600, 25, 16, 30),
+    (700, 16, 12, 30),
+    (800, 12, 6, 30),
+    (900, 12, 6, 17),
+];
+
+// gravity at a master level
+pub(crate) fn master_gravity(level: u32) -> u32 {
+    let (_, gravity) = MASTER_GRAVITY
+        .iter()
+        .rev()
+        .find(|(from, _)| level >= *from)
+        .unwrap_or(&MASTER_GRAVITY[0]);
+    *gravity
+}
+
+// ARE, line clear delay and lock delay at a master level
+pub(crate) fn master_delays(level: u32) -> (u32, u32, u32) {
+    let (_, are, line_clear_delay, lock_delay) = MASTER_DELAYS
+        .iter()
+        .rev()
+        .find(|(from, _, _, _)| level >= *from)
+        .unwrap_or(&MASTER_DELAYS[0]);
+    (*are, *line_clear_delay, *lock_delay)
+}
+
+#[wasm_bindgen]
+#[repr(u8)] // 1 byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameMode {
+    // play until top out
+    Endless = 0,
+    // clear the line goal as fast as possible
+    Sprint = 1,
+    // score as much as possible within the time limit
+    Ultra = 2,
+    // clear the garbage goal, the garbage is refilled from the bottom
+    Dig = 3,
+    // clear the line goal while the speed goes up with the level
+    Marathon = 4,
+    // reach level 999, the level goes up with every piece and line, up to 20G
+    Master = 5,
+    // solve a puzzle, loaded with `load_puzzle`
+    Puzzle = 6,
+}
+
+impl GameMode {
+    // timed runs whose results are compared, placements cannot be taken back
+    pub(crate) fn is_ranked(self) -> bool {
+        matches!(self, GameMode::Sprint | GameMode::Ultra | GameMode::Master)
+    }
+}
+
+#[wasm_bindgen]
+#[repr(u8)] // 1 byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameStatus {
+    Playing = 0,
+    // the goal of the mode is reached
+    Completed = 1,
+    GameOver = 2,
+}
+
+// summary of a game, for the results screen
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameResult {
+    mode: GameMode,
+    status: GameStatus,
+    score: i32,
+    lines: u32,
+    level: u32,
+    elapsed_ms: u32,
+    pieces_placed: u32,
+    piece_counts: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl GameResult {
+    pub fn get_mode(&self) -> GameMode {
+        self.mode
+    }
+
+    pub fn get_status(&self) -> GameStatus {
+        self.status
+    }
+
+    pub fn get_score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn get_lines(&self) -> u32 {
+        self.lines
+    }
+
+    pub fn get_level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn get_elapsed_ms(&self) -> u32 {
+        self.elapsed_ms
+    }
+
+    pub fn get_pieces_placed(&self) -> u32 {
+        self.pieces_placed
+    }
+
+    // indexed by ShapeType
+    pub fn get_piece_counts(&self) -> Vec<u32> {
+        self.piece_counts.clone()
+    }
+}
+
+// the gravity, delays and initial rotation and hold set before a mode with its own speed started
+#[derive(Debug, Copy, Clone)]
+struct Speed {
+    gravity: u32,
+    are: u32,
+    line_clear_delay: u32,
+    lock_delay: u32,
+    irs_enabled: bool,
+    ihs_enabled: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ModeState {
+    mode: GameMode,
+    line_goal: u32,
+    // 0 for no max level
+    max_level: u32,
+    time_limit_ms: u32,
+    garbage_goal: u32,
+    // garbage rows kept on the board while there is garbage left to dig
+    garbage_height: usize,
+    garbage_added: u32,
+    garbage: GarbageGenerator,
+    // draws the dig garbage apart from the shapes, so digging faster does not change them
+    garbage_rng: SeededRng,
+    // level of master mode, it goes up with pieces and lines instead of every 10 lines
+    master_level: u32,
+    completed: bool,
+    // put back when another mode is set
+    config_speed: Option<Speed>,
+}
+
+impl ModeState {
+    pub(crate) fn new() -> ModeState {
+        ModeState {
+            mode: GameMode::Endless,
+            line_goal: 0,
+            max_level: 0,
+            time_limit_ms: 0,
+            garbage_goal: 0,
+            garbage_height: 0,
+            garbage_added: 0,
+            garbage: GarbageGenerator::new(GarbagePattern::Messy),
+            garbage_rng: SeededRng::from_entropy(),
+            master_level: 0,
+            completed: false,
+            config_speed: None,
+        }
+    }
+
+    // another stream than the shapes and the versus garbage of the same seed
+    pub(crate) fn set_seed(&mut self, seed: u32) {
+        self.garbage_rng = SeededRng::seed_from_u64(u64::from(seed) ^ DIG_SEED);
+    }
+
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u8(self.mode as u8);
+        hasher.write_u32(self.line_goal);
+        hasher.write_u32(self.max_level);
+        hasher.write_u32(self.time_limit_ms);
+        hasher.write_u32(self.garbage_goal);
+        hasher.write_usize(self.garbage_height);
+        hasher.write_u32(self.garbage_added);
+        self.garbage.hash_state(hasher);
+        self.garbage_rng.hash_state(hasher);
+        hasher.write_u32(self.master_level);
+        hasher.write_bool(self.completed);
+        match self.config_speed {
+            Some(speed) => {
+                hasher.write_u8(1);
+                hasher.write_u32(speed.gravity);
+                hasher.write_u32(speed.are);
+                hasher.write_u32(speed.line_clear_delay);
+                hasher.write_u32(speed.lock_delay);
+                hasher.write_bool(speed.irs_enabled);
+                hasher.write_bool(speed.ihs_enabled);
+            }
+            None => hasher.write_u8(0),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Board {
+    pub fn get_mode(&self) -> GameMode {
+        self.mode.mode
+    }
+
+    // should be set before the game starts, it also resets the goals and undo to the mode default
+    pub fn set_mode(&mut self, mode: GameMode) {
+        if let Some(speed) = self.mode.config_speed.take() {
+            self.set_speed(speed);
+        }
+        if mode == GameMode::Marathon || mode == GameMode::Master {
+            self.mode.config_speed = Some(self.speed());
+        }
+        self.mode.mode = mode;
+        self.set_undo_enabled(!mode.is_ranked());
+        self.mode.completed = false;
+        if mode != GameMode::Puzzle {
+            self.puzzle = None;
+        }
+        self.mode.line_goal = match mode {
+            GameMode::Sprint => SPRINT_LINE_GOAL,
+            GameMode::Marathon => MARATHON_LINE_GOAL,
+            _ => 0,
+        };
+        self.mode.max_level = match mode {
+            GameMode::Marathon => MARATHON_MAX_LEVEL,
+            _ => 0,
+        };
+        if mode == GameMode::Marathon {
+            self.set_gravity(gravity_for_level(self.get_level()));
+        }
+        self.mode.time_limit_ms = match mode {
+            GameMode::Ultra => ULTRA_TIME_LIMIT_MS,
+            _ => 0,
+        };
+        let (garbage_goal, garbage_height) = match mode {
+            GameMode::Dig => (DIG_GARBAGE_GOAL, DIG_GARBAGE_HEIGHT),
+            _ => (0, 0),
+        };
+        self.mode.garbage_goal = garbage_goal;
+        self.mode.garbage_height = garbage_height;
+        self.mode.master_level = 0;
+        if mode == GameMode::Master {
+            self.apply_master_speed();
+            // at 20G a shape cannot be turned or held after it spawns
+            self.set_irs_enabled(true);
+            self.set_ihs_enabled(true);
+        }
+    }
+
+    pub fn get_line_goal(&self) -> u32 {
+        self.mode.line_goal
+    }
+
+    pub fn set_line_goal(&mut self, line_goal: u32) {
+        self.mode.line_goal = line_goal;
+    }
+
+    pub fn get_max_level(&self) -> u32 {
+        self.mode.max_level
+    }
+
+    pub fn set_max_level(&mut self, max_level: u32) {
+        self.mode.max_level = max_level;
+    }
+
+    pub fn get_time_limit_ms(&self) -> u32 {
+        self.mode.time_limit_ms
+    }
+
+    pub fn set_time_limit_ms(&mut self, time_limit_ms: u32) {
+        self.mode.time_limit_ms = time_limit_ms;
+    }
+
+    pub fn get_garbage_goal(&self) -> u32 {
+        self.mode.garbage_goal
+    }
+
+    pub fn set_garbage_goal(&mut self, garbage_goal: u32) {
+        self.mode.garbage_goal = garbage_goal;
+    }
+
+    pub fn get_garbage_height(&self) -> usize {
+        self.mode.garbage_height
+    }
+
+    pub fn set_garbage_height(&mut self, garbage_height: usize) {
+        self.mode.garbage_height = garbage_height;
+    }
+
+    pub fn get_master_level(&self) -> u32 {
+        self.mode.master_level
+    }
+
+    pub fn set_master_level(&mut self, master_level: u32) {
+        self.mode.master_level = master_level.min(MASTER_MAX_LEVEL);
+        if self.mode.mode == GameMode::Master {
+            self.apply_master_speed();
+        }
+    }
+
+    pub fn get_garbage_pattern(&self) -> GarbagePattern {
+        self.mode.garbage.get_pattern()
+    }
+
+    pub fn set_garbage_pattern(&mut self, pattern: GarbagePattern) {
+        self.mode.garbage = GarbageGenerator::new(pattern);
+    }
+
+    // garbage rows still to clear in dig mode
+    pub fn get_garbage_left(&self) -> u32 {
+        self.mode
+            .garbage_goal
+            .saturating_sub(self.get_garbage_cleared())
+    }
+
+    // only timed modes have a time limit
+    pub fn get_time_left_ms(&self) -> Option<u32> {
+        match self.mode.mode {
+            GameMode::Ultra => Some(
+                self.mode
+                    .time_limit_ms
+                    .saturating_sub(self.get_elapsed_ms()),
+            ),
+            _ => None,
+        }
+    }
+
+    pub fn get_status(&self) -> GameStatus {
+        if self.is_game_over() {
+            GameStatus::GameOver
+        } else if self.mode.completed {
+            GameStatus::Completed
+        } else {
+            GameStatus::Playing
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.get_status() != GameStatus::Playing
+    }
+
+    pub fn get_result(&self) -> GameResult {
+        GameResult {
+            mode: self.get_mode(),
+            status: self.get_status(),
+            score: self.get_score(),
+            lines: self.get_lines(),
+            level: self.get_level(),
+            elapsed_ms: self.get_elapsed_ms(),
+            pieces_placed: self.get_pieces_placed(),
+            piece_counts: self.get_piece_counts().to_vec(),
+        }
+    }
+
+    // pieces per second
+    pub fn get_pps(&self) -> f64 {
+        if self.get_elapsed_ms() == 0 {
+            return 0.0;
+        }
+        f64::from(self.get_pieces_placed()) * 1000.0 / f64::from(self.get_elapsed_ms())
+    }
+}
+
+impl Board {
+    // called after rows are deleted and when the clock advances
+    pub(crate) fn check_mode_goal(&mut self) {
+        if self.is_game_over() {
+            return;
+        }
+        let reached = match self.mode.mode {
+            GameMode::Endless => false,
+            GameMode::Sprint | GameMode::Marathon => self.get_lines() >= self.mode.line_goal,
+            GameMode::Ultra => self.get_elapsed_ms() >= self.mode.time_limit_ms,
+            GameMode::Dig => self.get_garbage_cleared() >= self.mode.garbage_goal,
+            GameMode::Master => self.mode.master_level >= MASTER_MAX_LEVEL,
+            GameMode::Puzzle => self.is_puzzle_solved(),
+        };
+        if reached && !self.mode.completed {
+            self.mode.completed = true;
+            let shape_type = self.get_running_shape().shape_type;
+            self.push_event(GameEvent::new(EventKind::Completed, shape_type));
+        }
+    }
+}
+
+impl Board {
+    // a new shape advances the master level, except at the end of a section (x99) and at 998
+    pub(crate) fn advance_level_on_spawn(&mut self) {
+        let level = self.mode.master_level;
+        if self.mode.mode != GameMode::Master || level % 100 == 99 || level >= MASTER_MAX_LEVEL - 1
+        {
+            return;
+        }
+        self.set_master_level(level + 1);
+    }
+
+    // each cleared line advances the master level, also past the end of a section, the marathon
+    // gravity follows the level
+    pub(crate) fn advance_level_on_clear(&mut self, deleted_rows: u32) {
+        if self.mode.mode == GameMode::Marathon {
+            self.set_gravity(gravity_for_level(self.get_level()));
+        }
+        if self.mode.mode != GameMode::Master || deleted_rows == 0 {
+            return;
+        }
+        let level = self.mode.master_level;
+        self.set_master_level(level + deleted_rows);
+        if self.mode.master_level / 100 > level / 100 {
+            let shape_type = self.get_running_shape().shape_type;
+            self.push_event(
+                GameEvent::new(EventKind::LevelUp, shape_type).with_level(self.mode.master_level),
+            );
+        }
+    }
+
+    fn speed(&self) -> Speed {
+        Speed {
+            gravity: self.get_gravity(),
+            are: self.get_are(),
+            line_clear_delay: self.get_line_clear_delay(),
+            lock_delay: self.get_lock_delay(),
+            irs_enabled: self.is_irs_enabled(),
+            ihs_enabled: self.is_ihs_enabled(),
+        }
+    }
+
+    fn set_speed(&mut self, speed: Speed) {
+        self.set_gravity(speed.gravity);
+        self.set_are(speed.are);
+        self.set_line_clear_delay(speed.line_clear_delay);
+        self.set_lock_delay(speed.lock_delay);
+        self.set_irs_enabled(speed.irs_enabled);
+        self.set_ihs_enabled(speed.ihs_enabled);
+    }
+
+    fn apply_master_speed(&mut self) {
+        let level = self.mode.master_level;
+        self.set_gravity(master_gravity(level));
+        let (are, line_clear_delay, lock_delay) = master_delays(level);
+        self.set_are(are);
+        self.set_line_clear_delay(line_clear_delay);
+        self.set_lock_delay(lock_delay);
+    }
+}
+
+impl Board {
+    // called before a shape spawns, tops the garbage back up to the garbage height in dig mode
+    pub(crate) fn refill_garbage(&mut self) {
+        if self.mode.mode != GameMode::Dig {
+            return;
+        }
+        // leave room for the spawning shape
+        let height = self
+            .mode
+            .garbage_height
+            .min(self.get_visible_height().saturating_sub(4));
+        let missing = height.saturating_sub(self.count_garbage_rows());
+        let not_added = self
+            .mode
+            .garbage_goal
+            .saturating_sub(self.mode.garbage_added) as usize;
+        let count = missing.min(not_added);
+        if count == 0 {
+            return;
+        }
+
+        let width = self.get_width();
+        let mut rows = vec![];
+        for _ in 0..count {
+            rows.push(
+                self.mode
+                    .garbage
+                    .next_row(&mut self.mode.garbage_rng, width),
+            );
+        }
+        self.push_garbage_rows(rows);
+        self.mode.garbage_added += count as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Direction;
+
+    // drop squares from left to right, clearing two lines per row of squares
+    fn clear_lines_with_squares(board: &mut Board, pieces: usize) {
+        board.set_next_shape_type(ShapeType::Square);
+        for piece in 0..pieces {
+            if !board.tick() {
+                return;
+            }
+            for _ in 0..4 {
+                board.move_shape(Direction::Left);
+            }
+            for _ in 0..(piece % 4) * 2 {
+                board.move_shape(Direction::Right);
+            }
+            board.move_shape(Direction::Down);
+            board.update(100);
+        }
+        board.tick();
+    }
+
+    #[test]
+    fn test_sprint_completes() {
+        let mut board = Board::new(8, 10);
+        board.set_mode(GameMode::Sprint);
+        assert_eq!(board.get_line_goal(), SPRINT_LINE_GOAL);
+        board.set_line_goal(4);
+        clear_lines_with_squares(&mut board, 100);
+
+        assert_eq!(board.get_status(), GameStatus::Completed);
+        assert!(!board.is_game_over());
+        assert_eq!(board.get_lines(), 4);
+        assert_eq!(board.get_pieces_placed(), 8);
+        assert_eq!(board.get_elapsed_ms(), 800);
+        assert!((board.get_pps() - 10.0).abs() < 1e-9);
+
+        // the clock stops once finished
+        assert!(!board.update(100));
+        assert_eq!(board.get_elapsed_ms(), 800);
+        let completed = board
+            .take_events()
+            .iter()
+            .filter(|event| event.get_kind() == EventKind::Completed)
+            .count();
+        assert_eq!(completed, 1);
+    }
+
+    #[test]
+    fn test_ultra_ends_at_time_limit() {
+        let mut board = Board::new(8, 10);
+        board.set_mode(GameMode::Ultra);
+        assert_eq!(board.get_time_limit_ms(), ULTRA_TIME_LIMIT_MS);
+        board.set_time_limit_ms(1000);
+        clear_lines_with_squares(&mut board, 4);
+        assert_eq!(board.get_time_left_ms(), Some(600));
+        assert_eq!(board.get_status(), GameStatus::Playing);
+
+        // the clock does not run past the limit
+        assert!(!board.update(5000));
+        assert_eq!(board.get_status(), GameStatus::Completed);
+        assert_eq!(board.get_elapsed_ms(), 1000);
+        assert_eq!(board.get_time_left_ms(), Some(0));
+        assert_eq!(board.get_score(), 2);
+        assert!(!board.tick());
+    }
+
+    #[test]
+    fn test_dig_fills_and_refills() {
+        let mut board = Board::new(8, 10);
+        board.set_seed(3);
+        board.set_mode(GameMode::Dig);
+        board.set_garbage_goal(5);
+        board.set_garbage_height(3);
+        board.set_garbage_pattern(GarbagePattern::SingleHole);
+        board.set_next_shape_type(ShapeType::Line);
+        assert!(board.tick());
+        assert_eq!(board.count_garbage_rows(), 3);
+        assert_eq!(board.get_garbage_left(), 5);
+
+        // fill the well of the bottom 3 rows with the vertical line
+        let well = (0..8)
+            .find(|&j| *board.get_cell(9, j) == Cell::Empty)
+            .unwrap();
+        for _ in 0..8 {
+            board.move_shape(Direction::Left);
+        }
+        for _ in 0..well {
+            board.move_shape(Direction::Right);
+        }
+        board.move_shape(Direction::Down);
+        assert!(board.tick());
+        assert_eq!(board.get_garbage_cleared(), 3);
+        assert_eq!(board.get_garbage_left(), 2);
+        // only 2 rows of garbage are left to add
+        assert_eq!(board.count_garbage_rows(), 2);
+        assert_eq!(*board.get_cell(7, well), Cell::Placed);
+        assert_eq!(*board.get_cell(9, well), Cell::Empty);
+        assert_eq!(board.get_status(), GameStatus::Playing);
+    }
+
+    #[test]
+    fn test_dig_keeps_the_shapes() {
+        let shapes = |mode| {
+            let mut board = Board::guideline();
+            board.set_seed(5);
+            board.set_mode(mode);
+            board.set_garbage_height(4);
+            (0..6)
+                .map(|_| {
+                    board.tick();
+                    let shape_type = board.get_running_shape().shape_type;
+                    board.move_shape(Direction::Down);
+                    shape_type
+                })
+                .collect::<Vec<_>>()
+        };
+        // the garbage rows come from their own generator
+        assert_eq!(shapes(GameMode::Dig), shapes(GameMode::Endless));
+    }
+
+    #[test]
+    fn test_dig_completes() {
+        let mut board = Board::new(8, 10);
+        board.set_mode(GameMode::Dig);
+        assert_eq!(board.get_garbage_goal(), DIG_GARBAGE_GOAL);
+        assert_eq!(board.get_garbage_height(), DIG_GARBAGE_HEIGHT);
+        board.set_garbage_goal(2);
+        board.set_garbage_pattern(GarbagePattern::Checker);
+        board.set_next_shape_type(ShapeType::Square);
+        assert!(board.tick());
+        assert_eq!(board.count_garbage_rows(), 2);
+
+        // fill the checker holes of the two garbage rows by hand and let a square finish them
+        board.move_shape(Direction::Left);
+        board.move_shape(Direction::Left);
+        board.move_shape(Direction::Left);
+        for i in 8..10 {
+            for j in 2..8 {
+                if *board.get_cell(i, j) == Cell::Empty {
+                    board.set_cell(i, j, Cell::Placed);
+                }
+            }
+        }
+        board.set_cell(8, 0, Cell::Empty);
+        board.set_cell(8, 1, Cell::Empty);
+        board.set_cell(9, 0, Cell::Empty);
+        board.set_cell(9, 1, Cell::Empty);
+        board.move_shape(Direction::Down);
+        assert!(!board.tick());
+        assert_eq!(board.get_status(), GameStatus::Completed);
+        assert_eq!(board.get_garbage_cleared(), 2);
+    }
+
+    #[test]
+    fn test_marathon() {
+        let mut board = Board::new(8, 10);
+        board.set_mode(GameMode::Marathon);
+        assert_eq!(board.get_line_goal(), MARATHON_LINE_GOAL);
+        assert_eq!(board.get_max_level(), MARATHON_MAX_LEVEL);
+        assert_eq!(board.get_gravity(), gravity_for_level(1));
+
+        board.set_line_goal(200);
+        board.set_max_level(3);
+        board.set_lines(19);
+        clear_lines_with_squares(&mut board, 4);
+        assert_eq!(board.get_level(), 3);
+        assert_eq!(board.get_gravity(), gravity_for_level(3));
+
+        // the level does not go past the max level
+        clear_lines_with_squares(&mut board, 40);
+        assert_eq!(board.get_lines(), 41);
+        assert_eq!(board.get_level(), 3);
+        assert_eq!(board.get_status(), GameStatus::Playing);
+
+        board.set_line_goal(43);
+        clear_lines_with_squares(&mut board, 40);
+        assert_eq!(board.get_status(), GameStatus::Completed);
+        assert!(!board.is_game_over());
+    }
+
+    #[test]
+    fn test_mode_speed_is_put_back() {
+        let mut board = Board::new(8, 10);
+        board.set_gravity(GRAVITY_UNIT / 10);
+        board.set_lock_delay(12);
+        board.set_mode(GameMode::Marathon);
+        board.set_lines(50);
+        // the level only sets the gravity when lines are cleared
+        board.check_mode_goal();
+        assert_eq!(board.get_gravity(), gravity_for_level(1));
+        clear_lines_with_squares(&mut board, 4);
+        assert_eq!(board.get_gravity(), gravity_for_level(6));
+
+        board.set_mode(GameMode::Master);
+        assert_eq!(board.get_gravity(), master_gravity(0));
+        assert_eq!(board.get_lock_delay(), master_delays(0).2);
+        board.set_mode(GameMode::Sprint);
+        assert_eq!(board.get_gravity(), GRAVITY_UNIT / 10);
+        assert_eq!(board.get_lock_delay(), 12);
+        assert!(!board.is_irs_enabled());
+    }
+
+    #[test]
+    fn test_result() {
+        let mut board = Board::new(8, 10);
+        board.set_mode(GameMode::Marathon);
+        board.set_line_goal(4);
+        clear_lines_with_squares(&mut board, 100);
+
+        let result = board.get_result();
+        assert_eq!(result.get_mode(), GameMode::Marathon);
+        assert_eq!(result.get_status(), GameStatus::Completed);
+        assert_eq!(result.get_score(), 4);
+        assert_eq!(result.get_lines(), 4);
+        assert_eq!(result.get_level(), 1);
+        assert_eq!(result.get_elapsed_ms(), 800);
+        assert_eq!(result.get_pieces_placed(), 8);
+        assert_eq!(result.get_piece_counts(), vec![8, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(board.get_piece_count(ShapeType::Square), 8);
+        assert_eq!(board.get_piece_count(ShapeType::Random), 0);
+    }
+
+    #[test]
+    fn test_master_level() {
+        let mut board = Board::new(8, 10);
+        board.set_mode(GameMode::Master);
+        assert_eq!(board.get_level(), 0);
+        assert_eq!(board.get_gravity(), 4);
+        assert_eq!(board.get_are(), 25);
+        assert_eq!(board.get_line_clear_delay(), 40);
+        assert_eq!(board.get_lock_delay(), 30);
+        assert!(board.is_irs_enabled());
+        assert!(board.is_ihs_enabled());
+
+        // one level per shape, stopping at the end of the section
+        board.set_master_level(97);
+        board.advance_level_on_spawn();
+        assert_eq!(board.get_level(), 98);
+        board.advance_level_on_spawn();
+        board.advance_level_on_spawn();
+        assert_eq!(board.get_level(), 99);
+
+        // lines go past the end of the section
+        board.take_events();
+        board.advance_level_on_clear(2);
+        assert_eq!(board.get_level(), 101);
+        assert_eq!(board.get_gravity(), 80);
+        let events = board.take_events();
+        assert_eq!(events[0].get_kind(), EventKind::LevelUp);
+        assert_eq!(events[0].get_level(), 101);
+
+        board.set_master_level(500);
+        assert_eq!(board.get_gravity(), INSTANT_GRAVITY);
+        assert_eq!(board.get_line_clear_delay(), 25);
+        board.set_master_level(900);
+        assert_eq!(board.get_are(), 12);
+        assert_eq!(board.get_lock_delay(), 17);
+
+        board.set_master_level(998);
+        board.advance_level_on_spawn();
+        assert_eq!(board.get_level(), 998);
+        assert_eq!(board.get_status(), GameStatus::Playing);
+        board.advance_level_on_clear(4);
+        board.check_mode_goal();
+        assert_eq!(board.get_level(), MASTER_MAX_LEVEL);
+        assert_eq!(board.get_status(), GameStatus::Completed);
+    }
+
+    #[test]
+    fn test_master_game() {
+        let mut board = Board::new(8, 10);
+        board.set_mode(GameMode::Master);
+        board.set_master_level(500);
+        board.set_next_shape_type(ShapeType::Square);
+        // 25 frames of ARE, then the shape falls to the bottom at once
+        board.update(400);
+        assert!(board.is_in_entry_delay());
+        board.update(50);
+        assert_eq!(board.get_level(), 501);
+        assert_eq!(*board.get_cell(9, 3), Cell::Running);
+        // and locks after the lock delay
+        board.update(500);
+        assert_eq!(*board.get_cell(9, 3), Cell::Placed);
+        assert!(board.is_in_entry_delay());
+    }
+
+    #[test]
+    fn test_endless_has_no_goal() {
+        let mut board = Board::new(8, 10);
+        board.set_mode(GameMode::Sprint);
+        board.set_mode(GameMode::Endless);
+        board.set_line_goal(4);
+        clear_lines_with_squares(&mut board, 12);
+        assert_eq!(board.get_status(), GameStatus::Playing);
+        assert_eq!(board.get_lines(), 6);
+    }
+
+    #[test]
+    fn test_ranked_modes_disable_undo() {
+        let mut board = Board::new(8, 10);
+        for &mode in [GameMode::Sprint, GameMode::Ultra, GameMode::Master].iter() {
+            board.set_mode(mode);
+            assert!(!board.is_undo_enabled());
+        }
+        board.set_mode(GameMode::Marathon);
+        assert!(board.is_undo_enabled());
+
+        board.set_mode(GameMode::Sprint);
+        board.set_next_shape_type(ShapeType::Square);
+        board.tick();
+        board.move_shape(Direction::Down);
+        assert!(!board.undo());
+    }
+}
@@ -0,0 +1,854 @@
+// networked versus matches in lockstep: every client simulates all the boards of the match from
+// the inputs of all the players, the seeded boards make them play out the same everywhere
+use crate::config::GameConfig;
+use crate::input::Key;
+use crate::tetris::*;
+use crate::versus::Match;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Uint8Array;
+#[cfg(target_arch = "wasm32")]
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+#[cfg(target_arch = "wasm32")]
+use std::rc::Rc;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsCast;
+#[cfg(target_arch = "wasm32")]
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+// every frame of a match is this long on every client
+pub const FRAME_MS: u32 = 16;
+// frames a client can send ahead of the slowest player
+pub const MAX_FRAMES_AHEAD: u32 = 8;
+// the match is hashed every frame, and the hash sent to the others every this many frames
+pub const HASH_INTERVAL: u32 = 60;
+// the frame of the game over the relay sends for a player who left the match
+pub const LEFT_FRAME: u32 = u32::MAX;
+
+// in the order of their values
+const KEYS: [Key; 6] = [
+    Key::Left,
+    Key::Right,
+    Key::SoftDrop,
+    Key::HardDrop,
+    Key::Rotate,
+    Key::Hold,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    // false when the key is released
+    pub pressed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetMessage {
+    // client to relay, asks for a seat in the next match
+    Join,
+    // relay to every client once the match is full
+    Start {
+        player: u8,
+        players: u8,
+        seed: u32,
+    },
+    // the keys a player pressed and released during a frame
+    Inputs {
+        player: u8,
+        frame: u32,
+        events: Vec<KeyEvent>,
+    },
+    // garbage a player sent during a frame, checked by the others against their simulation
+    Garbage {
+        player: u8,
+        frame: u32,
+        rows: u16,
+    },
+    // a player topped out during a frame
+    GameOver {
+        player: u8,
+        frame: u32,
+    },
+    // the state hash of the whole match after a frame, see src/hash.rs
+    Hash {
+        player: u8,
+        frame: u32,
+        hash: u64,
+    },
+}
+
+impl NetMessage {
+    // a tag byte, then the fields in little endian, a key event is the key with the top bit set
+    // when it is pressed
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        match self {
+            NetMessage::Join => bytes.push(0),
+            NetMessage::Start {
+                player,
+                players,
+                seed,
+            } => {
+                bytes.extend(&[1, *player, *players]);
+                bytes.extend(&seed.to_le_bytes());
+            }
+            NetMessage::Inputs {
+                player,
+                frame,
+                events,
+            } => {
+                bytes.extend(&[2, *player]);
+                bytes.extend(&frame.to_le_bytes());
+                bytes.push(events.len() as u8);
+                for event in events {
+                    let pressed = if event.pressed { 0x80 } else { 0 };
+                    bytes.push(event.key as u8 | pressed);
+                }
+            }
+            NetMessage::Garbage {
+                player,
+                frame,
+                rows,
+            } => {
+                bytes.extend(&[3, *player]);
+                bytes.extend(&frame.to_le_bytes());
+                bytes.extend(&rows.to_le_bytes());
+            }
+            NetMessage::GameOver { player, frame } => {
+                bytes.extend(&[4, *player]);
+                bytes.extend(&frame.to_le_bytes());
+            }
+            NetMessage::Hash {
+                player,
+                frame,
+                hash,
+            } => {
+                bytes.extend(&[5, *player]);
+                bytes.extend(&frame.to_le_bytes());
+                bytes.extend(&hash.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<NetMessage, TetrisError> {
+        let mut reader = Reader { bytes };
+        let message = match reader.u8()? {
+            0 => NetMessage::Join,
+            1 => NetMessage::Start {
+                player: reader.u8()?,
+                players: reader.u8()?,
+                seed: reader.u32()?,
+            },
+            2 => {
+                let player = reader.u8()?;
+                let frame = reader.u32()?;
+                let mut events = vec![];
+                for _ in 0..reader.u8()? {
+                    let byte = reader.u8()?;
+                    let key = *KEYS
+                        .get((byte & 0x7f) as usize)
+                        .ok_or(TetrisError::InvalidParam)?;
+                    events.push(KeyEvent {
+                        key,
+                        pressed: byte & 0x80 != 0,
+                    });
+                }
+                NetMessage::Inputs {
+                    player,
+                    frame,
+                    events,
+                }
+            }
+            3 => NetMessage::Garbage {
+                player: reader.u8()?,
+                frame: reader.u32()?,
+                rows: reader.u16()?,
+            },
+            4 => NetMessage::GameOver {
+                player: reader.u8()?,
+                frame: reader.u32()?,
+            },
+            5 => NetMessage::Hash {
+                player: reader.u8()?,
+                frame: reader.u32()?,
+                hash: reader.u64()?,
+            },
+            _ => return Err(TetrisError::InvalidParam),
+        };
+        if !reader.bytes.is_empty() {
+            return Err(TetrisError::InvalidParam);
+        }
+        Ok(message)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], TetrisError> {
+        if self.bytes.len() < count {
+            return Err(TetrisError::InvalidParam);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, TetrisError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, TetrisError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, TetrisError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, TetrisError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+// the match as one client sees it, a frame is only simulated once the inputs of every player
+// for it have arrived
+#[derive(Debug, Clone)]
+pub struct Lockstep {
+    player: usize,
+    versus: Match,
+    // next frame to simulate, and next frame of the local player to send
+    frame: u32,
+    sent_frame: u32,
+    elapsed_ms: u32,
+    pending: Vec<KeyEvent>,
+    // inputs of every player from `frame` on
+    inputs: Vec<VecDeque<Vec<KeyEvent>>>,
+    // garbage sent by a player during a simulated frame
+    attacks: HashMap<(usize, u32), u32>,
+    // state hash after the last simulated frame, and after the frames the hash is sent for
+    state_hash: u64,
+    hashes: HashMap<u32, u64>,
+    // garbage, game over and hash messages of the other players not checked yet
+    reports: Vec<NetMessage>,
+    outbox: Vec<NetMessage>,
+    game_over_sent: bool,
+    desynced: bool,
+    // players who left the match, their inputs will not come
+    left: Vec<bool>,
+}
+
+impl Lockstep {
+    pub fn new(
+        player: usize,
+        players: usize,
+        seed: u32,
+        config: &GameConfig,
+    ) -> Result<Lockstep, TetrisError> {
+        if player >= players {
+            return Err(TetrisError::InvalidParam);
+        }
+        let mut versus = Match::try_new(players, config)?;
+        versus.set_seed(seed);
+        Ok(Lockstep {
+            player,
+            versus,
+            frame: 0,
+            sent_frame: 0,
+            elapsed_ms: 0,
+            pending: vec![],
+            inputs: vec![VecDeque::new(); players],
+            attacks: HashMap::new(),
+            state_hash: 0,
+            hashes: HashMap::new(),
+            reports: vec![],
+            outbox: vec![],
+            game_over_sent: false,
+            desynced: false,
+            left: vec![false; players],
+        })
+    }
+
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn versus(&self) -> &Match {
+        &self.versus
+    }
+
+    pub fn state_hash(&self) -> u64 {
+        self.state_hash
+    }
+
+    // a garbage, game over or hash message did not match the simulation
+    pub fn is_desynced(&self) -> bool {
+        self.desynced
+    }
+
+    pub fn has_left(&self, player: usize) -> bool {
+        self.left.get(player).copied().unwrap_or(false)
+    }
+
+    pub fn key_down(&mut self, key: Key) {
+        self.pending.push(KeyEvent { key, pressed: true });
+    }
+
+    pub fn key_up(&mut self, key: Key) {
+        self.pending.push(KeyEvent {
+            key,
+            pressed: false,
+        });
+    }
+
+    // send the local inputs of every frame that has passed, then simulate what can be
+    pub fn advance(&mut self, delta_ms: u32) {
+        self.elapsed_ms = (self.elapsed_ms + delta_ms).min(FRAME_MS * MAX_FRAMES_AHEAD);
+        while self.elapsed_ms >= FRAME_MS && self.sent_frame < self.frame + MAX_FRAMES_AHEAD {
+            self.elapsed_ms -= FRAME_MS;
+            let events: Vec<KeyEvent> = self.pending.drain(..).collect();
+            self.inputs[self.player].push_back(events.clone());
+            self.outbox.push(NetMessage::Inputs {
+                player: self.player as u8,
+                frame: self.sent_frame,
+                events,
+            });
+            self.sent_frame += 1;
+        }
+        self.simulate();
+    }
+
+    // a message of another player, relayed in the order it was sent
+    pub fn receive(&mut self, message: NetMessage) -> Result<(), TetrisError> {
+        match message {
+            NetMessage::Inputs {
+                player,
+                frame,
+                events,
+            } => {
+                let player = player as usize;
+                if player == self.player || player >= self.inputs.len() {
+                    return Err(TetrisError::InvalidParam);
+                }
+                if frame != self.frame + self.inputs[player].len() as u32 {
+                    return Err(TetrisError::InvalidIndex);
+                }
+                self.inputs[player].push_back(events);
+            }
+            NetMessage::GameOver {
+                player,
+                frame: LEFT_FRAME,
+            } => {
+                let player = player as usize;
+                if player == self.player || player >= self.inputs.len() {
+                    return Err(TetrisError::InvalidParam);
+                }
+                self.left[player] = true;
+            }
+            NetMessage::Garbage { player, .. }
+            | NetMessage::GameOver { player, .. }
+            | NetMessage::Hash { player, .. } => {
+                // only the other players report, a bad index would panic in `check_reports`
+                let player = player as usize;
+                if player == self.player || player >= self.inputs.len() {
+                    return Err(TetrisError::InvalidParam);
+                }
+                self.reports.push(message)
+            }
+            NetMessage::Join | NetMessage::Start { .. } => return Err(TetrisError::InvalidParam),
+        }
+        self.simulate();
+        Ok(())
+    }
+
+    // messages for the other players
+    pub fn take_outbox(&mut self) -> Vec<NetMessage> {
+        self.outbox.drain(..).collect()
+    }
+
+    fn simulate(&mut self) {
+        while !self.versus.is_finished() && self.inputs.iter().all(|inputs| !inputs.is_empty()) {
+            self.step();
+        }
+        self.check_reports();
+    }
+
+    fn step(&mut self) {
+        let time_ms = self.frame * FRAME_MS;
+        let players = self.inputs.len();
+        let sent: Vec<u32> = (0..players)
+            .map(|player| self.versus.board(player).get_garbage_sent())
+            .collect();
+        for player in 0..players {
+            let events = self.inputs[player].pop_front().unwrap_or_default();
+            for event in events {
+                if event.pressed {
+                    self.versus.key_down(player, event.key, time_ms);
+                } else {
+                    self.versus.key_up(player, event.key, time_ms);
+                }
+            }
+        }
+        self.versus.update(FRAME_MS, time_ms);
+
+        for (player, &before) in sent.iter().enumerate() {
+            let rows = self.versus.board(player).get_garbage_sent() - before;
+            if rows == 0 {
+                continue;
+            }
+            self.attacks.insert((player, self.frame), rows);
+            if player == self.player {
+                self.outbox.push(NetMessage::Garbage {
+                    player: player as u8,
+                    frame: self.frame,
+                    rows: rows as u16,
+                });
+            }
+        }
+        if !self.game_over_sent && self.versus.board(self.player).is_finished() {
+            self.game_over_sent = true;
+            self.outbox.push(NetMessage::GameOver {
+                player: self.player as u8,
+                frame: self.frame,
+            });
+        }
+        self.state_hash = self.versus.state_hash();
+        if self.frame.is_multiple_of(HASH_INTERVAL) {
+            self.hashes.insert(self.frame, self.state_hash);
+            self.outbox.push(NetMessage::Hash {
+                player: self.player as u8,
+                frame: self.frame,
+                hash: self.state_hash,
+            });
+        }
+        self.frame += 1;
+    }
+
+    // compare the reports of frames already simulated
+    fn check_reports(&mut self) {
+        let frame = self.frame;
+        let attacks = &self.attacks;
+        let hashes = &self.hashes;
+        let versus = &self.versus;
+        let mut desynced = false;
+        self.reports.retain(|report| match *report {
+            NetMessage::Garbage {
+                player,
+                frame: sent,
+                rows,
+            } if sent < frame => {
+                desynced |= attacks.get(&(player as usize, sent)) != Some(&u32::from(rows));
+                false
+            }
+            NetMessage::GameOver {
+                player,
+                frame: over,
+            } if over < frame => {
+                desynced |= !versus.board(player as usize).is_finished();
+                false
+            }
+            NetMessage::Hash {
+                frame: hashed,
+                hash,
+                ..
+            } if hashed < frame => {
+                desynced |= hashes.get(&hashed) != Some(&hash);
+                false
+            }
+            _ => true,
+        });
+        self.desynced |= desynced;
+    }
+}
+
+// a browser client of the relay server, see src/bin/relay.rs
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub struct NetClient {
+    socket: WebSocket,
+    state: Rc<RefCell<ClientState>>,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+#[cfg(target_arch = "wasm32")]
+struct ClientState {
+    config: GameConfig,
+    // set once the relay starts the match
+    lockstep: Option<Lockstep>,
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl NetClient {
+    // connect to the relay, for example ws://localhost:9001, and join the next match
+    #[wasm_bindgen(constructor)]
+    pub fn new(url: &str, config: &GameConfig) -> Result<NetClient, JsValue> {
+        Board::try_from_config(config)?;
+        let socket = WebSocket::new(url)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        let state = Rc::new(RefCell::new(ClientState {
+            config: config.clone(),
+            lockstep: None,
+        }));
+
+        let join = socket.clone();
+        let on_open = Closure::wrap(Box::new(move || {
+            let _ = join.send_with_u8_array(&NetMessage::Join.encode());
+        }) as Box<dyn FnMut()>);
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+
+        let received = state.clone();
+        let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+            let bytes = Uint8Array::new(&event.data()).to_vec();
+            // messages that cannot be decoded or played are dropped
+            if let Ok(message) = NetMessage::decode(&bytes) {
+                received.borrow_mut().receive(message);
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        Ok(NetClient {
+            socket,
+            state,
+            _on_open: on_open,
+            _on_message: on_message,
+        })
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.state.borrow().lockstep.is_some()
+    }
+
+    pub fn get_player(&self) -> Option<usize> {
+        self.state.borrow().lockstep.as_ref().map(Lockstep::player)
+    }
+
+    pub fn get_players(&self) -> usize {
+        self.with_match(Match::get_players).unwrap_or(0)
+    }
+
+    pub fn key_down(&mut self, key: Key) {
+        if let Some(lockstep) = self.state.borrow_mut().lockstep.as_mut() {
+            lockstep.key_down(key);
+        }
+    }
+
+    pub fn key_up(&mut self, key: Key) {
+        if let Some(lockstep) = self.state.borrow_mut().lockstep.as_mut() {
+            lockstep.key_up(key);
+        }
+    }
+
+    // send the inputs of the frames that have passed, return false once the match is over
+    pub fn update(&mut self, delta_ms: u32) -> bool {
+        let outbox = match self.state.borrow_mut().lockstep.as_mut() {
+            Some(lockstep) => {
+                lockstep.advance(delta_ms);
+                lockstep.take_outbox()
+            }
+            None => return true,
+        };
+        for message in outbox {
+            let _ = self.socket.send_with_u8_array(&message.encode());
+        }
+        !self.is_finished()
+    }
+
+    pub fn render(&self, player: usize) -> String {
+        self.with_match(|versus| versus.render(player))
+            .unwrap_or_default()
+    }
+
+    // a copy, which JS has to free, use `get_score` for every frame
+    pub fn get_board(&self, player: usize) -> Option<Board> {
+        self.with_match(|versus| versus.get_board(player))
+    }
+
+    pub fn get_score(&self, player: usize) -> i32 {
+        self.with_match(|versus| versus.get_score(player))
+            .unwrap_or(0)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.with_match(Match::is_finished).unwrap_or(false)
+    }
+
+    pub fn get_winner(&self) -> Option<usize> {
+        self.with_match(Match::get_winner).flatten()
+    }
+
+    // the relay lost the connection of the player
+    pub fn has_left(&self, player: usize) -> bool {
+        match self.state.borrow().lockstep.as_ref() {
+            Some(lockstep) => lockstep.has_left(player),
+            None => false,
+        }
+    }
+
+    pub fn is_desynced(&self) -> bool {
+        match self.state.borrow().lockstep.as_ref() {
+            Some(lockstep) => lockstep.is_desynced(),
+            None => false,
+        }
+    }
+
+    // the client can be freed after this, the socket no longer calls back into it
+    pub fn close(&self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        let _ = self.socket.close();
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl NetClient {
+    fn with_match<T>(&self, f: impl FnOnce(&Match) -> T) -> Option<T> {
+        self.state
+            .borrow()
+            .lockstep
+            .as_ref()
+            .map(|lockstep| f(lockstep.versus()))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl ClientState {
+    fn receive(&mut self, message: NetMessage) {
+        match (message, self.lockstep.as_mut()) {
+            (
+                NetMessage::Start {
+                    player,
+                    players,
+                    seed,
+                },
+                None,
+            ) => {
+                self.lockstep =
+                    Lockstep::new(player as usize, players as usize, seed, &self.config).ok();
+            }
+            (message, Some(lockstep)) => {
+                let _ = lockstep.receive(message);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let messages = vec![
+            NetMessage::Join,
+            NetMessage::Start {
+                player: 1,
+                players: 2,
+                seed: 0xdead_beef,
+            },
+            NetMessage::Inputs {
+                player: 0,
+                frame: 300,
+                events: vec![
+                    KeyEvent {
+                        key: Key::Left,
+                        pressed: true,
+                    },
+                    KeyEvent {
+                        key: Key::Hold,
+                        pressed: false,
+                    },
+                ],
+            },
+            NetMessage::Garbage {
+                player: 1,
+                frame: 7,
+                rows: 4,
+            },
+            NetMessage::GameOver {
+                player: 0,
+                frame: 1000,
+            },
+            NetMessage::Hash {
+                player: 1,
+                frame: 60,
+                hash: 0x0123_4567_89ab_cdef,
+            },
+        ];
+        for message in messages {
+            assert_eq!(NetMessage::decode(&message.encode()), Ok(message));
+        }
+        // an empty frame is 7 bytes
+        let inputs = NetMessage::Inputs {
+            player: 0,
+            frame: 1,
+            events: vec![],
+        };
+        assert_eq!(inputs.encode().len(), 7);
+
+        assert!(NetMessage::decode(&[]).is_err());
+        assert!(NetMessage::decode(&[9]).is_err());
+        assert!(NetMessage::decode(&[0, 0]).is_err());
+        assert!(NetMessage::decode(&[1, 0, 2]).is_err());
+        assert!(NetMessage::decode(&[2, 0, 1, 0, 0, 0, 1, 6]).is_err());
+    }
+
+    // deliver the messages of every client to the others
+    fn exchange(clients: &mut [Lockstep]) {
+        for i in 0..clients.len() {
+            for message in clients[i].take_outbox() {
+                for (j, client) in clients.iter_mut().enumerate() {
+                    if j != i {
+                        client.receive(message.clone()).unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lockstep() {
+        let config = GameConfig::guideline();
+        let mut clients: Vec<Lockstep> = (0..2)
+            .map(|player| Lockstep::new(player, 2, 5, &config).unwrap())
+            .collect();
+        // hard drops in the same column top out after about 180 frames
+        for frame in 0..150 {
+            if frame % 20 == 0 {
+                clients[0].key_down(Key::HardDrop);
+                clients[1].key_down(Key::Left);
+            } else if frame % 20 == 1 {
+                clients[0].key_up(Key::HardDrop);
+                clients[1].key_up(Key::Left);
+            }
+            // the second player lags a frame behind
+            clients[0].advance(FRAME_MS);
+            if frame > 0 {
+                clients[1].advance(FRAME_MS);
+            }
+            exchange(&mut clients);
+        }
+        assert_eq!(clients[0].frame(), 149);
+        clients[1].advance(FRAME_MS);
+        exchange(&mut clients);
+
+        for player in 0..2 {
+            let boards: Vec<&Board> = clients
+                .iter()
+                .map(|client| client.versus().board(player))
+                .collect();
+            assert_eq!(boards[0].get_cells(), boards[1].get_cells());
+            assert_eq!(boards[0].get_score(), boards[1].get_score());
+        }
+        assert_eq!(clients[0].frame(), clients[1].frame());
+        assert_eq!(clients[0].state_hash(), clients[1].state_hash());
+        assert!(clients[0].versus().board(0).get_pieces_placed() > 5);
+        assert!(clients.iter().all(|client| !client.is_desynced()));
+
+        // inputs out of order or from the client itself
+        let inputs = NetMessage::Inputs {
+            player: 1,
+            frame: 0,
+            events: vec![],
+        };
+        assert_eq!(
+            clients[0].receive(inputs.clone()),
+            Err(TetrisError::InvalidIndex)
+        );
+        assert_eq!(clients[1].receive(inputs), Err(TetrisError::InvalidParam));
+    }
+
+    #[test]
+    fn test_desync() {
+        let config = GameConfig::guideline();
+        let mut client = Lockstep::new(0, 2, 5, &config).unwrap();
+        client
+            .receive(NetMessage::Garbage {
+                player: 1,
+                frame: 0,
+                rows: 4,
+            })
+            .unwrap();
+        client.advance(FRAME_MS);
+        assert!(!client.is_desynced());
+        client
+            .receive(NetMessage::Inputs {
+                player: 1,
+                frame: 0,
+                events: vec![],
+            })
+            .unwrap();
+        // the other player cannot have sent garbage in the first frame
+        assert_eq!(client.frame(), 1);
+        assert!(client.is_desynced());
+
+        // the first frame is hashed
+        let mut client = Lockstep::new(0, 2, 5, &config).unwrap();
+        let mut other = Lockstep::new(1, 2, 5, &config).unwrap();
+        client.advance(FRAME_MS);
+        other.advance(FRAME_MS);
+        for message in other.take_outbox() {
+            client.receive(message).unwrap();
+        }
+        assert_eq!(client.frame(), 1);
+        assert!(!client.is_desynced());
+        client
+            .receive(NetMessage::Hash {
+                player: 1,
+                frame: 0,
+                hash: client.state_hash() ^ 1,
+            })
+            .unwrap();
+        assert!(client.is_desynced());
+    }
+
+    #[test]
+    fn test_report_player() {
+        let config = GameConfig::guideline();
+        let mut client = Lockstep::new(0, 2, 5, &config).unwrap();
+        // reports of an unknown player or of the client itself
+        for &player in &[0, 2, 255] {
+            let reports = vec![
+                NetMessage::Garbage {
+                    player,
+                    frame: 0,
+                    rows: 4,
+                },
+                NetMessage::GameOver { player, frame: 0 },
+                NetMessage::Hash {
+                    player,
+                    frame: 0,
+                    hash: 0,
+                },
+            ];
+            for report in reports {
+                assert_eq!(client.receive(report), Err(TetrisError::InvalidParam));
+            }
+        }
+        client.advance(FRAME_MS);
+        client
+            .receive(NetMessage::Inputs {
+                player: 1,
+                frame: 0,
+                events: vec![],
+            })
+            .unwrap();
+        assert_eq!(client.frame(), 1);
+        assert!(!client.is_desynced());
+    }
+}
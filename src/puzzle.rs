@@ -0,0 +1,361 @@
+use crate::hash::StateHasher;
+use crate::mode::GameMode;
+use crate::tetris::*;
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[repr(u8)] // 1 byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PuzzleGoal {
+    // clear the goal count of lines
+    ClearLines = 0,
+    // clear two lines with a T-spin, the goal count of times
+    TSpinDouble = 1,
+    // clear lines until the board is empty, the goal count of times
+    PerfectClear = 2,
+}
+
+// a predefined layout with a fixed sequence of pieces, the pieces are also the piece budget
+#[derive(Debug, Clone)]
+pub(crate) struct Puzzle {
+    goal: PuzzleGoal,
+    goal_count: u32,
+    pieces: VecDeque<ShapeType>,
+    lines: u32,
+    t_spin_doubles: u32,
+    perfect_clears: u32,
+}
+
+impl Puzzle {
+    fn progress(&self) -> u32 {
+        match self.goal {
+            PuzzleGoal::ClearLines => self.lines,
+            PuzzleGoal::TSpinDouble => self.t_spin_doubles,
+            PuzzleGoal::PerfectClear => self.perfect_clears,
+        }
+    }
+
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u8(self.goal as u8);
+        hasher.write_u32(self.goal_count);
+        hasher.write_usize(self.pieces.len());
+        for &shape_type in self.pieces.iter() {
+            hasher.write_u8(shape_type as u8);
+        }
+        hasher.write_u32(self.lines);
+        hasher.write_u32(self.t_spin_doubles);
+        hasher.write_u32(self.perfect_clears);
+    }
+}
+
+// rows from top to bottom, '#' for a filled cell and '.' for an empty one
+fn parse_layout(layout: &str, width: usize) -> Result<Vec<Vec<Cell>>, TetrisError> {
+    layout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let row = line
+                .chars()
+                .map(|c| match c {
+                    '#' => Ok(Cell::Placed),
+                    '.' => Ok(Cell::Empty),
+                    _ => Err(TetrisError::InvalidParam),
+                })
+                .collect::<Result<Vec<Cell>, TetrisError>>()?;
+            if row.len() != width {
+                return Err(TetrisError::InvalidParam);
+            }
+            Ok(row)
+        })
+        .collect()
+}
+
+// one letter per piece, I O T S Z L J, the engine L has its foot on the left like a guideline J
+fn parse_pieces(pieces: &str) -> Result<VecDeque<ShapeType>, TetrisError> {
+    pieces
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c.to_ascii_uppercase() {
+            'I' => Ok(ShapeType::Line),
+            'O' => Ok(ShapeType::Square),
+            'T' => Ok(ShapeType::T),
+            'S' => Ok(ShapeType::S),
+            'Z' => Ok(ShapeType::Z),
+            'L' => Ok(ShapeType::MirroredL),
+            'J' => Ok(ShapeType::L),
+            _ => Err(TetrisError::InvalidParam),
+        })
+        .collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl Board {
+    // start a puzzle on this board, the layout sits at the bottom of the visible field
+    pub fn load_puzzle(
+        &mut self,
+        layout: &str,
+        pieces: &str,
+        goal: PuzzleGoal,
+        goal_count: u32,
+    ) -> Result<(), JsValue> {
+        self.try_load_puzzle(layout, pieces, goal, goal_count)
+            .map_err(JsValue::from)
+    }
+}
+
+#[wasm_bindgen]
+impl Board {
+    pub fn get_puzzle_goal(&self) -> Option<PuzzleGoal> {
+        self.puzzle.as_ref().map(|puzzle| puzzle.goal)
+    }
+
+    pub fn get_puzzle_goal_count(&self) -> u32 {
+        self.puzzle.as_ref().map_or(0, |puzzle| puzzle.goal_count)
+    }
+
+    // lines, T-spin doubles or perfect clears so far, depending on the goal
+    pub fn get_puzzle_progress(&self) -> u32 {
+        self.puzzle.as_ref().map_or(0, Puzzle::progress)
+    }
+
+    // pieces not spawned yet
+    pub fn get_puzzle_pieces_left(&self) -> u32 {
+        self.puzzle
+            .as_ref()
+            .map_or(0, |puzzle| puzzle.pieces.len() as u32)
+    }
+}
+
+impl Board {
+    pub fn try_load_puzzle(
+        &mut self,
+        layout: &str,
+        pieces: &str,
+        goal: PuzzleGoal,
+        goal_count: u32,
+    ) -> Result<(), TetrisError> {
+        let rows = parse_layout(layout, self.get_width())?;
+        let pieces = parse_pieces(pieces)?;
+        if rows.len() > self.get_visible_height() || pieces.is_empty() || goal_count == 0 {
+            return Err(TetrisError::InvalidParam);
+        }
+
+        let mut cells = vec![vec![Cell::Empty; self.get_width()]; self.get_height() - rows.len()];
+        cells.extend(rows);
+        self.set_cells(cells);
+        self.set_running_cells(vec![]);
+        self.set_next_shape_type(pieces[0]);
+        self.set_hold_shape_type(None);
+        self.set_hold_used(false);
+        self.set_t_spin(false);
+        self.set_score(0);
+        self.set_lines(0);
+        self.set_pieces_placed(0);
+        self.set_piece_counts([0; 7]);
+        self.set_finesse_faults(0);
+        self.set_elapsed_ms(0);
+        self.set_game_over_reason(None);
+        self.set_mode(GameMode::Puzzle);
+        self.puzzle = Some(Puzzle {
+            goal,
+            goal_count,
+            pieces,
+            lines: 0,
+            t_spin_doubles: 0,
+            perfect_clears: 0,
+        });
+        self.clear_history();
+        Ok(())
+    }
+
+    // the next piece of the sequence, None when not playing a puzzle or no piece is left
+    pub(crate) fn next_puzzle_piece(&mut self) -> Option<ShapeType> {
+        if self.get_mode() != GameMode::Puzzle {
+            return None;
+        }
+        let puzzle = self.puzzle.as_mut()?;
+        let shape_type = puzzle.pieces.pop_front()?;
+        if let Some(&next_shape_type) = puzzle.pieces.front() {
+            self.set_next_shape_type(next_shape_type);
+        }
+        Some(shape_type)
+    }
+
+    pub(crate) fn is_out_of_puzzle_pieces(&self) -> bool {
+        self.get_mode() == GameMode::Puzzle && self.get_puzzle_pieces_left() == 0
+    }
+
+    // called after rows are deleted
+    pub(crate) fn record_puzzle_clear(&mut self, deleted_rows: usize) {
+        if self.get_mode() != GameMode::Puzzle || deleted_rows == 0 {
+            return;
+        }
+        let t_spin_double = self.is_t_spin() && deleted_rows == 2;
+        let perfect_clear = self
+            .get_cells()
+            .iter()
+            .all(|row| row.iter().all(|&cell| cell == Cell::Empty));
+        if let Some(puzzle) = self.puzzle.as_mut() {
+            puzzle.lines += deleted_rows as u32;
+            if t_spin_double {
+                puzzle.t_spin_doubles += 1;
+            }
+            if perfect_clear {
+                puzzle.perfect_clears += 1;
+            }
+        }
+    }
+
+    pub(crate) fn is_puzzle_solved(&self) -> bool {
+        match &self.puzzle {
+            Some(puzzle) => puzzle.progress() >= puzzle.goal_count,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Direction;
+    use crate::mode::GameStatus;
+
+    #[test]
+    fn test_parse_puzzle() {
+        let mut board = Board::new(4, 6);
+        assert_eq!(
+            board.try_load_puzzle("#.##\n##", "T", PuzzleGoal::ClearLines, 1),
+            Err(TetrisError::InvalidParam)
+        );
+        assert_eq!(
+            board.try_load_puzzle("#.#x", "T", PuzzleGoal::ClearLines, 1),
+            Err(TetrisError::InvalidParam)
+        );
+        assert_eq!(
+            board.try_load_puzzle("#.##", "TX", PuzzleGoal::ClearLines, 1),
+            Err(TetrisError::InvalidParam)
+        );
+        assert_eq!(
+            board.try_load_puzzle("#.##", "", PuzzleGoal::ClearLines, 1),
+            Err(TetrisError::InvalidParam)
+        );
+
+        let layout = "
+            ....
+            #..#
+            #.##
+        ";
+        assert!(board
+            .try_load_puzzle(layout, "T o", PuzzleGoal::ClearLines, 1)
+            .is_ok());
+        assert_eq!(board.get_mode(), GameMode::Puzzle);
+        assert_eq!(board.get_puzzle_goal(), Some(PuzzleGoal::ClearLines));
+        assert_eq!(board.get_puzzle_pieces_left(), 2);
+        assert_eq!(board.get_next_shape_type(), ShapeType::T);
+        assert_eq!(*board.get_cell(3, 0), Cell::Empty);
+        assert_eq!(*board.get_cell(4, 0), Cell::Placed);
+        assert_eq!(*board.get_cell(5, 1), Cell::Empty);
+        assert_eq!(*board.get_cell(5, 2), Cell::Placed);
+    }
+
+    #[test]
+    fn test_parse_puzzle_pieces() {
+        // guideline letters, as used by TBP and puzzle sites
+        assert_eq!(
+            parse_pieces("LJ").unwrap(),
+            vec![ShapeType::MirroredL, ShapeType::L]
+        );
+        assert_eq!(
+            parse_pieces("i o t s z").unwrap(),
+            vec![
+                ShapeType::Line,
+                ShapeType::Square,
+                ShapeType::T,
+                ShapeType::S,
+                ShapeType::Z
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clear_lines_puzzle() {
+        let mut board = Board::new(4, 6);
+        board
+            .try_load_puzzle("#..#\n#..#", "OT", PuzzleGoal::ClearLines, 2)
+            .unwrap();
+        assert!(board.tick());
+        assert_eq!(board.get_next_shape_type(), ShapeType::T);
+        assert_eq!(board.get_puzzle_pieces_left(), 1);
+        board.move_shape(Direction::Down);
+        assert!(!board.tick());
+        assert_eq!(board.get_puzzle_progress(), 2);
+        assert_eq!(board.get_status(), GameStatus::Completed);
+    }
+
+    #[test]
+    fn test_puzzle_out_of_pieces() {
+        let mut board = Board::new(4, 6);
+        board
+            .try_load_puzzle("#..#", "O", PuzzleGoal::ClearLines, 2)
+            .unwrap();
+        assert!(board.tick());
+        // the last piece cannot be held
+        board.hold();
+        assert_eq!(board.get_hold_shape_type(), None);
+        board.move_shape(Direction::Down);
+        assert!(!board.tick());
+        assert_eq!(board.get_puzzle_progress(), 1);
+        assert_eq!(board.get_status(), GameStatus::GameOver);
+        assert_eq!(
+            board.get_game_over_reason(),
+            Some(GameOverReason::OutOfPieces)
+        );
+    }
+
+    #[test]
+    fn test_t_spin_double_puzzle() {
+        let mut board = Board::new(6, 8);
+        let layout = "
+            ...#..
+            #...##
+            ##.###
+        ";
+        board
+            .try_load_puzzle(layout, "T", PuzzleGoal::TSpinDouble, 1)
+            .unwrap();
+        board.tick();
+        board.tick();
+        board.rotate();
+        while board.can_drop() {
+            board.tick();
+        }
+        // rotate under the overhang, back to the spawn orientation
+        for _ in 0..3 {
+            board.rotate();
+        }
+        assert_eq!(*board.get_cell(6, 3), Cell::Running);
+        assert!(!board.tick());
+        assert!(board.is_t_spin());
+        assert_eq!(board.get_lines(), 2);
+        assert_eq!(board.get_status(), GameStatus::Completed);
+    }
+
+    #[test]
+    fn test_perfect_clear_puzzle() {
+        let mut board = Board::new(4, 6);
+        board
+            .try_load_puzzle("#..#\n#..#", "SO", PuzzleGoal::PerfectClear, 1)
+            .unwrap();
+        // hold the S and use the square
+        board.tick();
+        board.hold();
+        assert_eq!(board.get_puzzle_pieces_left(), 0);
+        board.move_shape(Direction::Down);
+        assert!(!board.tick());
+        assert_eq!(board.get_status(), GameStatus::Completed);
+        assert_eq!(board.get_puzzle_progress(), 1);
+    }
+}
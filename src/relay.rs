@@ -0,0 +1,350 @@
+// relays the messages of networked matches between their players over WebSocket, the relay does
+// not simulate anything, the clients do, see src/net.rs
+use crate::net::{NetMessage, LEFT_FRAME};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+// how often a player thread looks for messages to forward
+const POLL_MS: u64 = 5;
+// how long a client has for the handshake and the join message, so a silent one does not hold
+// up the match
+const JOIN_TIMEOUT_MS: u64 = 3000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Relay {
+    pub players: usize,
+    pub seed: u32,
+}
+
+impl Relay {
+    // seat the players of one match in the order they join, then relay their messages until
+    // they all leave
+    pub fn serve_match(&self, listener: &TcpListener) -> io::Result<()> {
+        let sockets = self.seat(listener);
+        // the listener is left as it was given for the next match
+        listener.set_nonblocking(false)?;
+        let mut sockets = sockets?;
+
+        // the match needs every player, it is called off when one of them already left
+        for (player, socket) in sockets.iter_mut().enumerate() {
+            let start = NetMessage::Start {
+                player: player as u8,
+                players: self.players as u8,
+                seed: self.seed,
+            };
+            if socket.send(Message::Binary(start.encode())).is_err() {
+                for socket in sockets.iter_mut() {
+                    let _ = socket.close(None);
+                    let _ = socket.flush();
+                }
+                return Ok(());
+            }
+        }
+
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            (0..self.players).map(|_| mpsc::channel()).unzip();
+        let mut threads = vec![];
+        for (player, (socket, inbox)) in sockets.into_iter().zip(receivers).enumerate() {
+            let others: Vec<Sender<Vec<u8>>> = senders
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != player)
+                .map(|(_, sender)| sender.clone())
+                .collect();
+            threads.push(thread::spawn(move || relay(player, socket, inbox, others)));
+        }
+        drop(senders);
+        for thread in threads {
+            let _ = thread.join();
+        }
+        Ok(())
+    }
+
+    // every handshake runs on its own thread, so a slow or silent client does not keep the others
+    // from being seated, the clients still shaking hands once the match is full are turned away
+    fn seat(&self, listener: &TcpListener) -> io::Result<Vec<WebSocket<TcpStream>>> {
+        listener.set_nonblocking(true)?;
+        let (joined, seated) = mpsc::channel();
+        let mut sockets = vec![];
+        while sockets.len() < self.players {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let joined = joined.clone();
+                    thread::spawn(move || {
+                        if let Some(socket) = join(stream) {
+                            let _ = joined.send(socket);
+                        }
+                    });
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    if let Ok(socket) = seated.recv_timeout(Duration::from_millis(POLL_MS)) {
+                        sockets.push(socket);
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(sockets)
+    }
+}
+
+// the handshake and the join message, None if the client is not a player
+fn join(stream: TcpStream) -> Option<WebSocket<TcpStream>> {
+    // some platforms pass the non blocking mode of the listener on
+    stream.set_nonblocking(false).ok()?;
+    stream
+        .set_read_timeout(Some(Duration::from_millis(JOIN_TIMEOUT_MS)))
+        .ok()?;
+    let mut socket = tungstenite::accept(stream).ok()?;
+    match socket.read() {
+        Ok(Message::Binary(bytes)) if NetMessage::decode(&bytes) == Ok(NetMessage::Join) => {
+            socket
+                .get_ref()
+                .set_read_timeout(Some(Duration::from_millis(POLL_MS)))
+                .ok()?;
+            Some(socket)
+        }
+        _ => None,
+    }
+}
+
+// relay the messages of the player until it leaves, then tell the others, which would wait for
+// its inputs forever
+fn relay(
+    player: usize,
+    socket: WebSocket<TcpStream>,
+    inbox: Receiver<Vec<u8>>,
+    others: Vec<Sender<Vec<u8>>>,
+) {
+    forward(player, socket, inbox, &others);
+    let left = NetMessage::GameOver {
+        player: player as u8,
+        frame: LEFT_FRAME,
+    };
+    for other in others.iter() {
+        let _ = other.send(left.encode());
+    }
+}
+
+// forward what the player sends about itself to the others, and what they send to the player
+fn forward(
+    player: usize,
+    mut socket: WebSocket<TcpStream>,
+    inbox: Receiver<Vec<u8>>,
+    others: &[Sender<Vec<u8>>],
+) {
+    loop {
+        while let Ok(bytes) = inbox.try_recv() {
+            if socket.send(Message::Binary(bytes)).is_err() {
+                return;
+            }
+        }
+        let bytes = match socket.read() {
+            Ok(Message::Binary(bytes)) => bytes,
+            Ok(Message::Close(_)) => return,
+            Ok(_) => continue,
+            Err(tungstenite::Error::Io(ref err))
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                continue
+            }
+            Err(_) => return,
+        };
+        let sender = match NetMessage::decode(&bytes) {
+            Ok(NetMessage::Inputs { player, .. })
+            | Ok(NetMessage::Garbage { player, .. })
+            | Ok(NetMessage::GameOver { player, .. })
+            | Ok(NetMessage::Hash { player, .. }) => player as usize,
+            _ => continue,
+        };
+        // a player only speaks for itself
+        if sender != player {
+            continue;
+        }
+        for other in others.iter() {
+            // the other player may have left
+            let _ = other.send(bytes.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::input::Key;
+    use crate::net::{Lockstep, FRAME_MS};
+    use crate::tetris::Board;
+    use std::time::Instant;
+
+    struct Peer {
+        socket: WebSocket<TcpStream>,
+        lockstep: Lockstep,
+    }
+
+    fn send(socket: &mut WebSocket<TcpStream>, message: &NetMessage) {
+        socket.send(Message::Binary(message.encode())).unwrap();
+    }
+
+    fn read(socket: &mut WebSocket<TcpStream>) -> NetMessage {
+        loop {
+            if let Message::Binary(bytes) = socket.read().unwrap() {
+                return NetMessage::decode(&bytes).unwrap();
+            }
+        }
+    }
+
+    fn connect(port: u16) -> Peer {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let url = format!("ws://127.0.0.1:{}", port);
+        let (mut socket, _) = tungstenite::client(url.as_str(), stream).unwrap();
+        send(&mut socket, &NetMessage::Join);
+        let lockstep = match read(&mut socket) {
+            NetMessage::Start {
+                player,
+                players,
+                seed,
+            } => Lockstep::new(
+                player as usize,
+                players as usize,
+                seed,
+                &GameConfig::guideline(),
+            )
+            .unwrap(),
+            message => panic!("unexpected {:?}", message),
+        };
+        Peer { socket, lockstep }
+    }
+
+    #[test]
+    fn test_relay_match() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let relay = Relay {
+            players: 2,
+            seed: 3,
+        };
+        let server = thread::spawn(move || relay.serve_match(&listener));
+
+        // the second player is seated once the first one has joined
+        let first = thread::spawn(move || connect(port));
+        let second = connect(port);
+        let mut peers = vec![first.join().unwrap(), second];
+        peers.sort_by_key(|peer| peer.lockstep.player());
+
+        for frame in 0..150 {
+            for (i, peer) in peers.iter_mut().enumerate() {
+                if frame % (20 + i) == 0 {
+                    peer.lockstep.key_down(Key::HardDrop);
+                } else if frame % (20 + i) == 1 {
+                    peer.lockstep.key_up(Key::HardDrop);
+                }
+                peer.lockstep.advance(FRAME_MS);
+                for message in peer.lockstep.take_outbox() {
+                    send(&mut peer.socket, &message);
+                }
+            }
+            // wait for the inputs of the other player
+            for peer in peers.iter_mut() {
+                while peer.lockstep.frame() <= frame as u32 && !peer.lockstep.versus().is_finished()
+                {
+                    let message = read(&mut peer.socket);
+                    peer.lockstep.receive(message).unwrap();
+                }
+            }
+        }
+
+        for player in 0..2 {
+            let boards: Vec<&Board> = peers
+                .iter()
+                .map(|peer| peer.lockstep.versus().board(player))
+                .collect();
+            assert_eq!(boards[0].get_cells(), boards[1].get_cells());
+            assert_eq!(boards[0].get_pieces_placed(), boards[1].get_pieces_placed());
+            assert!(boards[0].get_pieces_placed() > 5);
+        }
+        assert_eq!(
+            peers[0].lockstep.state_hash(),
+            peers[1].lockstep.state_hash()
+        );
+        assert!(peers.iter().all(|peer| !peer.lockstep.is_desynced()));
+
+        for mut peer in peers {
+            peer.socket.close(None).unwrap();
+            let _ = peer.socket.flush();
+        }
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_player_leaves() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let relay = Relay {
+            players: 2,
+            seed: 3,
+        };
+        let server = thread::spawn(move || relay.serve_match(&listener));
+
+        let first = thread::spawn(move || connect(port));
+        let second = connect(port);
+        let mut peers = vec![first.join().unwrap(), second];
+        peers.sort_by_key(|peer| peer.lockstep.player());
+
+        for peer in peers.iter_mut() {
+            peer.lockstep.advance(FRAME_MS * 4);
+            for message in peer.lockstep.take_outbox() {
+                send(&mut peer.socket, &message);
+            }
+        }
+        // the second player leaves in the middle of the match
+        let mut gone = peers.pop().unwrap();
+        gone.socket.close(None).unwrap();
+        let _ = gone.socket.flush();
+
+        let mut peer = peers.pop().unwrap();
+        while !peer.lockstep.has_left(1) {
+            let message = read(&mut peer.socket);
+            peer.lockstep.receive(message).unwrap();
+        }
+        assert_eq!(peer.lockstep.frame(), 4);
+        assert!(!peer.lockstep.is_desynced());
+
+        peer.socket.close(None).unwrap();
+        let _ = peer.socket.flush();
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_silent_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let relay = Relay {
+            players: 2,
+            seed: 3,
+        };
+        let server = thread::spawn(move || relay.serve_match(&listener));
+
+        // connects but never shakes hands, the players behind it are seated without waiting for
+        // it to time out
+        let started = Instant::now();
+        let _silent = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let first = thread::spawn(move || connect(port));
+        let second = connect(port);
+        let mut peers = vec![first.join().unwrap(), second];
+        peers.sort_by_key(|peer| peer.lockstep.player());
+        assert_eq!(peers[1].lockstep.player(), 1);
+        assert!(started.elapsed() < Duration::from_millis(JOIN_TIMEOUT_MS));
+
+        for mut peer in peers {
+            peer.socket.close(None).unwrap();
+            let _ = peer.socket.flush();
+        }
+        server.join().unwrap().unwrap();
+    }
+}
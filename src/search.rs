@@ -0,0 +1,243 @@
+use crate::input::Key;
+use crate::tetris::*;
+use std::collections::{HashSet, VecDeque};
+use wasm_bindgen::prelude::*;
+
+// keys the search tries from every position, a soft drop goes to the floor so tucks and spins
+// under an overhang can be reached
+const SEARCH_KEYS: [Key; 4] = [Key::Left, Key::Right, Key::Rotate, Key::SoftDrop];
+
+// a place where the running shape can lock, and the shortest way to get there
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    inputs: Vec<Key>,
+    // cells of the shape once locked, sorted
+    cells: Vec<(usize, usize)>,
+    // the shape locks as a T-spin
+    t_spin: bool,
+}
+
+#[wasm_bindgen]
+impl Placement {
+    // the keys as `Key` values, the last one is a hard drop
+    pub fn get_inputs(&self) -> Vec<u8> {
+        self.inputs.iter().map(|&key| key as u8).collect()
+    }
+
+    // row and column of every cell, one after the other
+    pub fn get_cells(&self) -> Vec<u32> {
+        self.cells
+            .iter()
+            .flat_map(|&(i, j)| vec![i as u32, j as u32])
+            .collect()
+    }
+
+    pub fn is_t_spin(&self) -> bool {
+        self.t_spin
+    }
+}
+
+impl Placement {
+    pub fn inputs(&self) -> &[Key] {
+        &self.inputs
+    }
+
+    pub fn cells(&self) -> &[(usize, usize)] {
+        &self.cells
+    }
+}
+
+#[wasm_bindgen]
+impl Board {
+    // every place the running shape can lock in, see `find_placements`
+    pub fn get_placements(&self) -> Vec<JsValue> {
+        self.find_placements()
+            .into_iter()
+            .map(JsValue::from)
+            .collect()
+    }
+}
+
+impl Board {
+    // breadth first search over the moves and rotations of the running shape on a copy of the
+    // board, so the first path to a placement is the shortest one, the same cells locked with and
+    // without a T-spin are two placements
+    pub fn find_placements(&self) -> Vec<Placement> {
+        if self.get_running_cells().is_empty() {
+            return vec![];
+        }
+        let mut start = self.clone_without_history();
+        start.take_events();
+
+        let mut seen = HashSet::new();
+        seen.insert(search_state(&start));
+        let mut queue = VecDeque::new();
+        queue.push_back((start, vec![]));
+        let mut landed = HashSet::new();
+        let mut placements = vec![];
+        while let Some((board, inputs)) = queue.pop_front() {
+            let cells = drop_cells(&board, board.get_running_cells());
+            // a hard drop that moves the shape is not a rotation into place
+            let t_spin = !board.can_drop() && board.is_t_spin_lock();
+            if landed.insert((cells.clone(), t_spin)) {
+                let mut inputs = inputs.clone();
+                inputs.push(Key::HardDrop);
+                placements.push(Placement {
+                    inputs,
+                    cells,
+                    t_spin,
+                });
+            }
+            for &key in SEARCH_KEYS.iter() {
+                let mut next = board.clone();
+                next.press(key);
+                if seen.insert(search_state(&next)) {
+                    let mut inputs = inputs.clone();
+                    inputs.push(key);
+                    queue.push_back((next, inputs));
+                }
+            }
+        }
+        placements
+    }
+}
+
+// the same cells can be in another rotation state, which rotates differently, or be reached by
+// a rotation, which can lock a T as a T-spin
+type SearchState = (Vec<(usize, usize)>, Vec<(i32, i32)>, bool);
+
+fn search_state(board: &Board) -> SearchState {
+    let mut cells = board.get_running_cells().clone();
+    cells.sort();
+    (
+        cells,
+        board.get_running_shape().top_left_offset.clone(),
+        board.get_running_shape().shape_type == ShapeType::T && board.is_last_move_rotation(),
+    )
+}
+
+// where the cells stop when they fall straight down
+pub(crate) fn drop_cells(board: &Board, cells: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut cells = cells.to_vec();
+    while cells
+        .iter()
+        .all(|&(i, j)| i + 1 < board.get_height() && !board.get_cell(i + 1, j).is_filled())
+    {
+        for cell in cells.iter_mut() {
+            cell.0 += 1;
+        }
+    }
+    cells.sort();
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_straight_drops() {
+        let mut board = Board::guideline();
+        board.set_next_shape_type(ShapeType::Square);
+        board.tick();
+        let placements = board.find_placements();
+        // a square fits in 9 columns and does not rotate
+        assert_eq!(placements.len(), 9);
+        assert!(placements
+            .iter()
+            .all(|placement| placement.inputs().last() == Some(&Key::HardDrop)));
+        let cells: HashSet<_> = placements.iter().map(Placement::cells).collect();
+        assert_eq!(cells.len(), placements.len());
+
+        // the path takes the shape where the search says it locks
+        let placement = &placements[placements.len() - 1];
+        let mut played = board.clone();
+        played.play_inputs(&placement.inputs()[..placement.inputs().len() - 1]);
+        assert_eq!(
+            drop_cells(&played, played.get_running_cells()),
+            placement.cells()
+        );
+    }
+
+    #[test]
+    fn test_tuck_under_overhang() {
+        let mut board = Board::guideline();
+        let floor = board.get_height() - 1;
+        // a roof over the three left columns, with two rows under it open
+        for j in 0..3 {
+            board.set_cell(floor - 2, j, Cell::Placed);
+        }
+        board.set_next_shape_type(ShapeType::Square);
+        board.tick();
+        let placements = board.find_placements();
+        let tucked = placements
+            .iter()
+            .find(|placement| placement.cells().contains(&(floor, 0)))
+            .expect("the square can slide under the roof");
+        assert!(tucked.inputs().contains(&Key::SoftDrop));
+
+        let mut played = board.clone();
+        played.play_inputs(tucked.inputs());
+        played.tick();
+        assert_eq!(*played.get_cell(floor, 0), Cell::Placed);
+        assert_eq!(*played.get_cell(floor, 1), Cell::Placed);
+    }
+
+    #[test]
+    fn test_t_spin_slot() {
+        let mut board = Board::new(6, 8);
+        let layout = "
+            ...#..
+            #...##
+            ##.###
+        ";
+        board
+            .try_load_puzzle(layout, "T", crate::puzzle::PuzzleGoal::TSpinDouble, 1)
+            .unwrap();
+        board.tick();
+        board.tick();
+        let placements = board.find_placements();
+        let slot = placements
+            .iter()
+            .find(|placement| placement.cells() == [(6, 1), (6, 2), (6, 3), (7, 2)])
+            .expect("the T fits in the slot under the overhang");
+        assert_eq!(slot.inputs().iter().rev().nth(1), Some(&Key::Rotate));
+        assert!(slot.is_t_spin());
+
+        board.play_inputs(slot.inputs());
+        board.tick();
+        assert!(board.is_t_spin());
+        assert_eq!(board.get_lines(), 2);
+    }
+
+    #[test]
+    fn test_t_spin_and_slide() {
+        let mut board = Board::new(6, 8);
+        let layout = "
+            #..#.#
+            #.....
+            ##.#..
+        ";
+        board
+            .try_load_puzzle(layout, "T", crate::puzzle::PuzzleGoal::ClearLines, 1)
+            .unwrap();
+        board.tick();
+        let cells = [(5, 2), (6, 1), (6, 2), (7, 2)];
+        let placements: Vec<_> = board
+            .find_placements()
+            .into_iter()
+            .filter(|placement| placement.cells() == cells)
+            .collect();
+        // dropped in, or rotated into place at the bottom
+        assert_eq!(placements.len(), 2);
+        assert_ne!(placements[0].is_t_spin(), placements[1].is_t_spin());
+        for placement in placements.iter() {
+            let mut played = board.clone();
+            played.play_inputs(placement.inputs());
+            played.tick();
+            assert_eq!(*played.get_cell(7, 2), Cell::Placed);
+            assert_eq!(played.is_t_spin(), placement.is_t_spin());
+        }
+    }
+}
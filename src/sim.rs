@@ -0,0 +1,216 @@
+use crate::ai::Ai;
+use crate::config::GameConfig;
+use crate::input::Key;
+use crate::tetris::*;
+use std::sync::Arc;
+use std::thread;
+
+// plays the running shape, the keys are pressed one after the other
+pub trait Agent {
+    fn choose(&mut self, board: &Board) -> Vec<Key>;
+}
+
+impl Agent for Ai {
+    fn choose(&mut self, board: &Board) -> Vec<Key> {
+        self.best_placement(board)
+            .map(|placement| placement.inputs().to_vec())
+            .unwrap_or_default()
+    }
+}
+
+// games are stepped with `Board::tick`, one row per tick without timing, so the gravity, the
+// delays and the lock delay of the config do not apply, the AI hard drops its placements anyway
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub config: GameConfig,
+    pub games: u32,
+    // game i is played with seed first_seed + i
+    pub first_seed: u32,
+    // a game still running after this many pieces is stopped
+    pub max_pieces: u32,
+    pub threads: usize,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation {
+            config: GameConfig::guideline(),
+            games: 100,
+            first_seed: 0,
+            max_pieces: 1000,
+            threads: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameStats {
+    pub seed: u32,
+    pub lines: u32,
+    pub score: i32,
+    pub pieces: u32,
+    // None when the game was stopped at the piece limit
+    pub game_over_reason: Option<GameOverReason>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub games: u32,
+    pub average_lines: f64,
+    pub average_score: f64,
+    pub average_pieces: f64,
+    // games ended by each GameOverReason, and games stopped at the piece limit
+    pub block_outs: u32,
+    pub lock_outs: u32,
+    pub top_outs: u32,
+    pub out_of_pieces: u32,
+    pub stopped: u32,
+}
+
+impl Summary {
+    pub fn of(stats: &[GameStats]) -> Summary {
+        let mut summary = Summary::default();
+        if stats.is_empty() {
+            return summary;
+        }
+        let games = stats.len() as f64;
+        summary.games = stats.len() as u32;
+        summary.average_lines = stats.iter().map(|game| f64::from(game.lines)).sum::<f64>() / games;
+        summary.average_score = stats.iter().map(|game| f64::from(game.score)).sum::<f64>() / games;
+        summary.average_pieces =
+            stats.iter().map(|game| f64::from(game.pieces)).sum::<f64>() / games;
+        for game in stats {
+            match game.game_over_reason {
+                Some(GameOverReason::BlockOut) => summary.block_outs += 1,
+                Some(GameOverReason::LockOut) => summary.lock_outs += 1,
+                Some(GameOverReason::TopOut) => summary.top_outs += 1,
+                Some(GameOverReason::OutOfPieces) => summary.out_of_pieces += 1,
+                None => summary.stopped += 1,
+            }
+        }
+        summary
+    }
+}
+
+impl Simulation {
+    // play every game with a new agent per thread, the stats are in seed order
+    pub fn run<A, F>(&self, new_agent: F) -> Result<Vec<GameStats>, TetrisError>
+    where
+        A: Agent,
+        F: Fn() -> A + Send + Sync + 'static,
+    {
+        // check the config once instead of in every thread
+        Board::try_from_config(&self.config)?;
+        let threads = self.threads.max(1) as u32;
+        let simulation = Arc::new(self.clone());
+        let new_agent = Arc::new(new_agent);
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| {
+                let simulation = Arc::clone(&simulation);
+                let new_agent = Arc::clone(&new_agent);
+                thread::spawn(move || {
+                    let mut agent = new_agent();
+                    (thread..simulation.games)
+                        .step_by(threads as usize)
+                        .map(|game| {
+                            simulation.play(&mut agent, simulation.first_seed.wrapping_add(game))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut stats = vec![];
+        for handle in handles {
+            stats.extend(handle.join().expect("simulation thread panicked"));
+        }
+        stats.sort_by_key(|game| game.seed.wrapping_sub(self.first_seed));
+        Ok(stats)
+    }
+
+    pub fn play<A: Agent>(&self, agent: &mut A, seed: u32) -> GameStats {
+        let mut board = Board::try_from_config(&self.config).expect("config is checked by run");
+        board.set_seed(seed);
+        board.set_undo_enabled(false);
+        while board.get_pieces_placed() < self.max_pieces && board.tick() {
+            if board.get_running_cells().is_empty() {
+                continue;
+            }
+            let inputs = agent.choose(&board);
+            board.play_inputs(&inputs);
+            // events are not read by anyone here
+            board.take_events();
+        }
+        GameStats {
+            seed,
+            lines: board.get_lines(),
+            score: board.get_score(),
+            pieces: board.get_pieces_placed(),
+            game_over_reason: board.get_game_over_reason(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // drops every shape where it spawns
+    struct Dropper;
+
+    impl Agent for Dropper {
+        fn choose(&mut self, _board: &Board) -> Vec<Key> {
+            vec![Key::HardDrop]
+        }
+    }
+
+    #[test]
+    fn test_dropper_tops_out() {
+        let simulation = Simulation {
+            games: 5,
+            threads: 2,
+            ..Simulation::default()
+        };
+        let stats = simulation.run(|| Dropper).unwrap();
+        assert_eq!(
+            stats.iter().map(|game| game.seed).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4]
+        );
+        let summary = Summary::of(&stats);
+        assert_eq!(summary.games, 5);
+        assert_eq!(summary.stopped, 0);
+        assert_eq!(summary.average_lines, 0.0);
+        assert_eq!(summary.block_outs + summary.lock_outs, 5);
+    }
+
+    #[test]
+    fn test_same_seeds_same_games() {
+        let simulation = Simulation {
+            games: 3,
+            max_pieces: 30,
+            threads: 3,
+            ..Simulation::default()
+        };
+        let stats = simulation.run(Ai::new).unwrap();
+        let single = Simulation {
+            threads: 1,
+            ..simulation.clone()
+        };
+        assert_eq!(single.run(Ai::new).unwrap(), stats);
+        assert!(stats.iter().all(|game| game.pieces == 30));
+        assert_eq!(Summary::of(&stats).stopped, 3);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let mut config = GameConfig::guideline();
+        config.set_width(1);
+        let simulation = Simulation {
+            config,
+            ..Simulation::default()
+        };
+        assert_eq!(
+            simulation.run(|| Dropper).err(),
+            Some(TetrisError::WidthOutOfRange)
+        );
+    }
+}
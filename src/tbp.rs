@@ -0,0 +1,427 @@
+// Tetris Bot Protocol messages, see https://github.com/tetris-bot-protocol/tbp-spec
+// the board is the frontend, the bot runs in another process and talks JSON over stdin and stdout
+use crate::tetris::*;
+use serde::{Deserialize, Serialize};
+
+// TBP boards always have 40 rows
+pub const TBP_HEIGHT: usize = 40;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Piece {
+    I,
+    O,
+    T,
+    L,
+    J,
+    S,
+    Z,
+}
+
+impl Piece {
+    // the engine L has its foot on the left, which is a J in the guideline
+    pub fn from_shape_type(shape_type: ShapeType) -> Option<Piece> {
+        match shape_type {
+            ShapeType::Line => Some(Piece::I),
+            ShapeType::Square => Some(Piece::O),
+            ShapeType::T => Some(Piece::T),
+            ShapeType::MirroredL => Some(Piece::L),
+            ShapeType::L => Some(Piece::J),
+            ShapeType::S => Some(Piece::S),
+            ShapeType::Z => Some(Piece::Z),
+            ShapeType::Random => None,
+        }
+    }
+
+    pub fn shape_type(self) -> ShapeType {
+        match self {
+            Piece::I => ShapeType::Line,
+            Piece::O => ShapeType::Square,
+            Piece::T => ShapeType::T,
+            Piece::L => ShapeType::MirroredL,
+            Piece::J => ShapeType::L,
+            Piece::S => ShapeType::S,
+            Piece::Z => ShapeType::Z,
+        }
+    }
+
+    // cells around the rotation center facing north, x to the right and y up
+    fn north_cells(self) -> [(i32, i32); 4] {
+        match self {
+            Piece::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            Piece::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            Piece::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+            Piece::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+            Piece::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+            Piece::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+            Piece::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+const ORIENTATIONS: [Orientation; 4] = [
+    Orientation::North,
+    Orientation::East,
+    Orientation::South,
+    Orientation::West,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+// where the rotation center of a piece is, x from the left and y from the bottom
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    #[serde(rename = "type")]
+    pub piece: Piece,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Location {
+    // row and column of the cells on the board, None if a cell is off the board
+    pub fn cells(&self, board: &Board) -> Option<Vec<(usize, usize)>> {
+        let turns = ORIENTATIONS
+            .iter()
+            .position(|&orientation| orientation == self.orientation)
+            .unwrap_or(0);
+        let mut cells = vec![];
+        for &(dx, dy) in self.piece.north_cells().iter() {
+            // clockwise quarter turns
+            let (dx, dy) = (0..turns).fold((dx, dy), |(x, y), _| (y, -x));
+            let (x, y) = (self.x + dx, self.y + dy);
+            if x < 0 || y < 0 || x as usize >= board.get_width() || y as usize >= board.get_height()
+            {
+                return None;
+            }
+            cells.push((board.get_height() - 1 - y as usize, x as usize));
+        }
+        cells.sort();
+        Some(cells)
+    }
+
+    // the first orientation and center that covers the cells
+    pub fn from_cells(piece: Piece, cells: &[(usize, usize)], board: &Board) -> Option<Location> {
+        let mut cells = cells.to_vec();
+        cells.sort();
+        let &(row, column) = cells.first()?;
+        let (x, y) = (column as i32, (board.get_height() - 1 - row) as i32);
+        for &orientation in ORIENTATIONS.iter() {
+            // the center is at most 2 cells away from any cell of the piece
+            for dx in -2..=2 {
+                for dy in -2..=2 {
+                    let location = Location {
+                        piece,
+                        orientation,
+                        x: x + dx,
+                        y: y + dy,
+                    };
+                    if location.cells(board).as_ref() == Some(&cells) {
+                        return Some(location);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Move {
+    pub location: Location,
+    pub spin: Spin,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Start {
+    pub hold: Option<Piece>,
+    // the running piece first
+    pub queue: Vec<Piece>,
+    pub combo: u32,
+    pub back_to_back: bool,
+    // rows from the bottom, a letter for every filled cell
+    pub board: Vec<Vec<Option<char>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules,
+    Start(Start),
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mv: Move,
+    },
+    NewPiece {
+        piece: Piece,
+    },
+    Stop,
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Error {
+        reason: String,
+    },
+    Ready,
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        features: Vec<String>,
+    },
+    // the best move first
+    Suggestion {
+        moves: Vec<Move>,
+    },
+}
+
+impl Board {
+    // the state a bot starts thinking from, the board must have 40 rows
+    pub fn tbp_start(&self) -> Start {
+        let mut queue = vec![];
+        if !self.get_running_cells().is_empty() {
+            queue.extend(Piece::from_shape_type(self.get_running_shape().shape_type));
+        }
+        queue.extend(Piece::from_shape_type(self.get_next_shape_type()));
+        let board = self
+            .get_cells()
+            .iter()
+            .rev()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| if cell.is_filled() { Some('G') } else { None })
+                    .collect()
+            })
+            .collect();
+        Start {
+            hold: self.get_hold_shape_type().and_then(Piece::from_shape_type),
+            queue,
+            combo: 0,
+            back_to_back: false,
+            board,
+        }
+    }
+
+    // hold if the move is for the other piece, then take the running piece to the location with
+    // or without a spin, the engine has no mini spins so a mini is played as a T-spin and only a T
+    // spins, the board is left as it was if the move cannot be played
+    pub fn play_tbp_move(&mut self, mv: &Move) -> Result<(), TetrisError> {
+        if self.get_running_cells().is_empty() {
+            return Err(TetrisError::InvalidParam);
+        }
+        let mut board = self.clone();
+        let shape_type = mv.location.piece.shape_type();
+        if board.get_running_shape().shape_type != shape_type {
+            board.hold();
+        }
+        if board.get_running_shape().shape_type != shape_type {
+            return Err(TetrisError::InvalidParam);
+        }
+        let cells = mv.location.cells(&board).ok_or(TetrisError::InvalidParam)?;
+        let t_spin = shape_type == ShapeType::T && mv.spin != Spin::None;
+        let placement = board
+            .find_placements()
+            .into_iter()
+            .find(|placement| placement.cells() == &cells[..] && placement.is_t_spin() == t_spin)
+            .ok_or(TetrisError::InvalidParam)?;
+        board.play_inputs(placement.inputs());
+        *self = board;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIECES: [Piece; 7] = [
+        Piece::I,
+        Piece::O,
+        Piece::T,
+        Piece::L,
+        Piece::J,
+        Piece::S,
+        Piece::Z,
+    ];
+
+    #[test]
+    fn test_pieces() {
+        for &piece in PIECES.iter() {
+            assert_eq!(Piece::from_shape_type(piece.shape_type()), Some(piece));
+        }
+        assert_eq!(Piece::from_shape_type(ShapeType::Random), None);
+    }
+
+    #[test]
+    fn test_location() {
+        let board = Board::guideline();
+        let location = Location {
+            piece: Piece::T,
+            orientation: Orientation::North,
+            x: 4,
+            y: 0,
+        };
+        assert_eq!(
+            location.cells(&board),
+            Some(vec![(38, 4), (39, 3), (39, 4), (39, 5)])
+        );
+        let east = Location {
+            orientation: Orientation::East,
+            ..location
+        };
+        assert_eq!(east.cells(&board), None);
+        let east = Location { y: 1, ..east };
+        assert_eq!(
+            east.cells(&board),
+            Some(vec![(37, 4), (38, 4), (38, 5), (39, 4)])
+        );
+        assert_eq!(
+            Location::from_cells(Piece::T, &east.cells(&board).unwrap(), &board),
+            Some(east)
+        );
+    }
+
+    #[test]
+    fn test_spawned_shapes_match() {
+        // the pieces spawned by the engine are the guideline ones
+        for &piece in PIECES.iter() {
+            let mut board = Board::guideline();
+            board.set_next_shape_type(piece.shape_type());
+            board.tick();
+            let cells = board.get_running_cells().clone();
+            let location = Location::from_cells(piece, &cells, &board).unwrap();
+            let mut cells = cells;
+            cells.sort();
+            assert_eq!(location.cells(&board), Some(cells));
+        }
+    }
+
+    #[test]
+    fn test_messages() {
+        assert_eq!(
+            serde_json::to_string(&FrontendMessage::Suggest).unwrap(),
+            r#"{"type":"suggest"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&FrontendMessage::NewPiece { piece: Piece::J }).unwrap(),
+            r#"{"type":"new_piece","piece":"J"}"#
+        );
+        let suggestion: BotMessage = serde_json::from_str(
+            r#"{"type":"suggestion","moves":[{"location":{"type":"T","orientation":"south",
+            "x":4,"y":1},"spin":"none"}],"move_info":{"nodes":10}}"#,
+        )
+        .unwrap();
+        match suggestion {
+            BotMessage::Suggestion { moves } => {
+                assert_eq!(moves[0].location.orientation, Orientation::South)
+            }
+            message => panic!("unexpected {:?}", message),
+        }
+
+        let mut board = Board::guideline();
+        board.set_next_shape_type(ShapeType::L);
+        board.tick();
+        board.set_cell(39, 0, Cell::Garbage);
+        let start = board.tbp_start();
+        assert_eq!(start.queue[0], Piece::J);
+        assert_eq!(start.queue.len(), 2);
+        assert_eq!(start.board.len(), TBP_HEIGHT);
+        assert_eq!(start.board[0][0], Some('G'));
+        assert_eq!(start.board[0][1], None);
+        // the running piece is not part of the board
+        assert!(
+            start
+                .board
+                .iter()
+                .flatten()
+                .filter(|cell| cell.is_some())
+                .count()
+                == 1
+        );
+    }
+
+    #[test]
+    fn test_play_move() {
+        let mut board = Board::guideline();
+        board.set_next_shape_type(ShapeType::T);
+        board.tick();
+        board.set_next_shape_type(ShapeType::Line);
+        let mv = Move {
+            location: Location {
+                piece: Piece::I,
+                orientation: Orientation::North,
+                x: 1,
+                y: 0,
+            },
+            // bots can report a spin for any piece
+            spin: Spin::Mini,
+        };
+        // the I is played by holding the T, and without a spin
+        assert_eq!(board.play_tbp_move(&mv), Ok(()));
+        assert_eq!(board.get_hold_shape_type(), Some(ShapeType::T));
+        for j in 0..4 {
+            assert_eq!(*board.get_cell(39, j), Cell::Placed);
+        }
+
+        board.tick();
+        let floating = Move {
+            location: Location {
+                piece: Piece::T,
+                orientation: Orientation::North,
+                x: 4,
+                y: 10,
+            },
+            spin: Spin::None,
+        };
+        assert_eq!(
+            board.play_tbp_move(&floating),
+            Err(TetrisError::InvalidParam)
+        );
+        assert!(!board.is_hold_used());
+    }
+
+    #[test]
+    fn test_play_spin() {
+        let mut board = Board::new(6, 8);
+        let layout = "
+            ...#..
+            #...##
+            ##.###
+        ";
+        board
+            .try_load_puzzle(layout, "T", crate::puzzle::PuzzleGoal::TSpinDouble, 1)
+            .unwrap();
+        board.tick();
+        board.tick();
+        let slot = [(6, 1), (6, 2), (6, 3), (7, 2)];
+        let mut mv = Move {
+            location: Location::from_cells(Piece::T, &slot, &board).unwrap(),
+            spin: Spin::None,
+        };
+        // the slot under the overhang can only be reached with a spin
+        assert_eq!(board.play_tbp_move(&mv), Err(TetrisError::InvalidParam));
+        mv.spin = Spin::Full;
+        assert_eq!(board.play_tbp_move(&mv), Ok(()));
+        board.tick();
+        assert!(board.is_t_spin());
+        assert_eq!(board.get_lines(), 2);
+    }
+}
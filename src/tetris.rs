@@ -1,15 +1,65 @@
+use crate::config::{RotationSystem, ScoringSystem};
+use crate::event::*;
+use crate::finesse::Finesse;
+use crate::hash::SeededRng;
+use crate::history::History;
+use crate::mode::{GameMode, ModeState};
+use crate::puzzle::Puzzle;
+use crate::timing::Timing;
+use crate::versus::VersusState;
 use rand::{
     distributions::{Distribution, Standard},
     Rng,
 };
+use std::collections::VecDeque;
 use std::fmt;
 use wasm_bindgen::prelude::*;
 
+// guideline playfield is 20 visible rows with 20 hidden rows above
+pub const GUIDELINE_BUFFER_HEIGHT: usize = 20;
+
+// board size limits, the smallest board still fits a vertical line
+pub const MIN_WIDTH: usize = 4;
+pub const MAX_WIDTH: usize = 100;
+pub const MIN_HEIGHT: usize = 4;
+pub const MAX_HEIGHT: usize = 1000;
+
 // Error
 #[derive(Debug, PartialEq)]
 pub enum TetrisError {
     InvalidParam,
     InvalidIndex,
+    WidthOutOfRange,
+    HeightOutOfRange,
+    InvalidConfig,
+}
+
+impl fmt::Display for TetrisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TetrisError::InvalidParam => write!(f, "invalid parameter"),
+            TetrisError::InvalidIndex => write!(f, "invalid index"),
+            TetrisError::WidthOutOfRange => write!(
+                f,
+                "board width must be between {} and {}",
+                MIN_WIDTH, MAX_WIDTH
+            ),
+            TetrisError::HeightOutOfRange => write!(
+                f,
+                "board height including buffer rows must be between {} and {}",
+                MIN_HEIGHT, MAX_HEIGHT
+            ),
+            TetrisError::InvalidConfig => write!(f, "invalid game config"),
+        }
+    }
+}
+
+// the JS versions of the `try_*` functions throw the message, they only exist in wasm builds
+// because a JsValue cannot be created anywhere else
+impl From<TetrisError> for JsValue {
+    fn from(error: TetrisError) -> JsValue {
+        JsValue::from_str(&error.to_string())
+    }
 }
 
 // Tetris board
@@ -20,7 +70,29 @@ pub enum Cell {
     Empty = 0,
     Running = 1,
     // for object which is dropping
-    Placed = 2, // already dropped cell
+    Placed = 2,  // already dropped cell
+    Garbage = 3, // rows pushed in from the bottom
+}
+
+impl Cell {
+    // cells that a shape cannot move into
+    pub fn is_filled(self) -> bool {
+        self == Cell::Placed || self == Cell::Garbage
+    }
+}
+
+#[wasm_bindgen]
+#[repr(u8)] // 1 byte
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameOverReason {
+    // a new shape overlaps the stack when it spawns
+    BlockOut = 0,
+    // a shape locks completely inside the hidden buffer rows
+    LockOut = 1,
+    // garbage pushes the stack past the top, or leaves no room for the running shape
+    TopOut = 2,
+    // the pieces of a puzzle ran out before its goal was reached
+    OutOfPieces = 3,
 }
 
 // a vector of positions
@@ -70,7 +142,7 @@ pub enum ShapeType {
 // for random value from ShapeType enum
 impl Distribution<ShapeType> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> ShapeType {
-        match rng.gen_range(0, 6) {
+        match rng.gen_range(0, 7) {
             0 => ShapeType::Square,
             1 => ShapeType::S,
             2 => ShapeType::Z,
@@ -132,36 +204,71 @@ pub fn generate_shape(shape_type: ShapeType) -> Shape {
 }
 
 #[wasm_bindgen]
+#[derive(Debug, Clone)]
 pub struct Board {
     width: usize,
+    // including the hidden buffer rows
     height: usize,
+    // rows above the visible field where shapes spawn
+    buffer_height: usize,
     cells: Vec<Vec<Cell>>,
+    // leftmost column of a new shape, centered when not set
+    spawn_column: Option<usize>,
+    scoring: ScoringSystem,
+    rotation_system: RotationSystem,
+    hold_enabled: bool,
     running_shape: Shape,
     next_shape_type: ShapeType,
     score: i32,
+    lines: u32,
+    pieces_placed: u32,
+    // placed shapes by ShapeType, without Random
+    piece_counts: [u32; 7],
+    // cleared rows that had garbage in them
+    garbage_cleared: u32,
+    // game time, advanced by `update`
+    elapsed_ms: u32,
+    hold_shape_type: Option<ShapeType>,
+    // a shape can only be held once until it is placed
+    hold_used: bool,
+    // the last successful movement of the running shape was a rotation
+    last_move_rotation: bool,
+    // the last locked shape was a T-spin
+    t_spin: bool,
+    game_over_reason: Option<GameOverReason>,
+    pub(crate) history: History,
+    pub(crate) events: VecDeque<GameEvent>,
+    pub(crate) finesse: Finesse,
+    pub(crate) mode: ModeState,
+    pub(crate) puzzle: Option<Puzzle>,
+    pub(crate) timing: Timing,
+    pub(crate) versus: VersusState,
+    pub(crate) rng: SeededRng,
 }
 
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 impl Board {
-    pub fn new(width: usize, height: usize) -> Board {
-        #[cfg(feature = "console_error_panic_hook")]
-        console_error_panic_hook::set_once();
+    #[wasm_bindgen(js_name = new)]
+    pub fn js_new(width: usize, height: usize) -> Result<Board, JsValue> {
+        Board::with_buffer(width, height, 0)
+    }
 
-        if width < 5 || width > 20 || height < 10 || height > 100 {
-            panic!(TetrisError::InvalidParam);
-        }
-        let cells = vec![vec![Cell::Empty; width]; height];
-        let score = 0;
-        let running_shape = generate_shape(ShapeType::Random);
-        let next_shape_type = ShapeType::Random;
-        Board {
-            width,
-            height,
-            cells,
-            running_shape,
-            next_shape_type,
-            score,
-        }
+    // a board with `buffer_height` hidden rows above the `height` visible ones
+    pub fn with_buffer(
+        width: usize,
+        height: usize,
+        buffer_height: usize,
+    ) -> Result<Board, JsValue> {
+        Board::try_new(width, height, buffer_height).map_err(JsValue::from)
+    }
+}
+
+#[wasm_bindgen]
+impl Board {
+    // standard 10 * 20 playfield with 20 hidden rows
+    pub fn guideline() -> Board {
+        Board::try_new(10, 20, GUIDELINE_BUFFER_HEIGHT).expect("guideline board size is valid")
     }
 
     pub fn get_width(&self) -> usize {
@@ -172,6 +279,48 @@ impl Board {
         self.height
     }
 
+    pub fn get_buffer_height(&self) -> usize {
+        self.buffer_height
+    }
+
+    pub fn get_visible_height(&self) -> usize {
+        self.height - self.buffer_height
+    }
+
+    pub fn get_spawn_column(&self) -> Option<usize> {
+        self.spawn_column
+    }
+
+    pub fn set_spawn_column(&mut self, spawn_column: Option<usize>) {
+        self.spawn_column = spawn_column;
+        self.finesse.clear_tables();
+    }
+
+    pub fn get_scoring(&self) -> ScoringSystem {
+        self.scoring
+    }
+
+    pub fn set_scoring(&mut self, scoring: ScoringSystem) {
+        self.scoring = scoring;
+    }
+
+    pub fn get_rotation_system(&self) -> RotationSystem {
+        self.rotation_system
+    }
+
+    pub fn set_rotation_system(&mut self, rotation_system: RotationSystem) {
+        self.rotation_system = rotation_system;
+        self.finesse.clear_tables();
+    }
+
+    pub fn is_hold_enabled(&self) -> bool {
+        self.hold_enabled
+    }
+
+    pub fn set_hold_enabled(&mut self, hold_enabled: bool) {
+        self.hold_enabled = hold_enabled;
+    }
+
     pub fn get_score(&self) -> i32 {
         self.score
     }
@@ -180,12 +329,183 @@ impl Board {
         self.next_shape_type
     }
 
+    pub fn get_running_shape_type(&self) -> ShapeType {
+        self.running_shape.shape_type
+    }
+
+    pub fn get_lines(&self) -> u32 {
+        self.lines
+    }
+
+    pub fn get_pieces_placed(&self) -> u32 {
+        self.pieces_placed
+    }
+
+    pub fn get_piece_count(&self, shape_type: ShapeType) -> u32 {
+        self.piece_counts
+            .get(shape_type as usize)
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    pub fn get_garbage_cleared(&self) -> u32 {
+        self.garbage_cleared
+    }
+
+    pub fn get_elapsed_ms(&self) -> u32 {
+        self.elapsed_ms
+    }
+
+    // same seed, same random shapes and garbage
+    pub fn set_seed(&mut self, seed: u32) {
+        self.rng = SeededRng::seed_from_u64(u64::from(seed));
+        self.versus.set_seed(seed);
+        self.mode.set_seed(seed);
+    }
+
+    // level goes up every 10 lines, up to the max level of the mode if any
+    pub fn get_level(&self) -> u32 {
+        if self.get_mode() == GameMode::Master {
+            return self.get_master_level();
+        }
+        let level = self.lines / 10 + 1;
+        match self.get_max_level() {
+            0 => level,
+            max_level => level.min(max_level),
+        }
+    }
+
+    pub fn get_hold_shape_type(&self) -> Option<ShapeType> {
+        self.hold_shape_type
+    }
+
+    pub fn is_t_spin(&self) -> bool {
+        self.t_spin
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.game_over_reason.is_some()
+    }
+
+    pub fn get_game_over_reason(&self) -> Option<GameOverReason> {
+        self.game_over_reason
+    }
+
+    // only the visible rows, the buffer rows are hidden
     pub fn render(&self) -> String {
         self.to_string()
     }
+
+    // all rows including the buffer, for debugging
+    pub fn render_with_buffer(&self) -> String {
+        format!("{:#}", self)
+    }
+
+    // visible cells row by row, one byte per cell
+    pub fn get_visible_cells(&self) -> Vec<u8> {
+        self.cells[self.buffer_height..]
+            .iter()
+            .flat_map(|row| row.iter().map(|&cell| cell as u8))
+            .collect()
+    }
 }
 
 impl Board {
+    // a board without buffer rows, panics on the sizes `try_new` rejects
+    pub fn new(width: usize, height: usize) -> Board {
+        Board::try_new(width, height, 0).expect("board size is valid")
+    }
+
+    // width is 4 to 100 columns, and the visible plus buffer rows are 4 to 1000 rows
+    pub fn try_new(
+        width: usize,
+        height: usize,
+        buffer_height: usize,
+    ) -> Result<Board, TetrisError> {
+        #[cfg(feature = "console_error_panic_hook")]
+        console_error_panic_hook::set_once();
+
+        if !(MIN_WIDTH..=MAX_WIDTH).contains(&width) {
+            return Err(TetrisError::WidthOutOfRange);
+        }
+        let height = height + buffer_height;
+        if !(MIN_HEIGHT..=MAX_HEIGHT).contains(&height) {
+            return Err(TetrisError::HeightOutOfRange);
+        }
+        let cells = vec![vec![Cell::Empty; width]; height];
+        let score = 0;
+        // nothing runs until the first shape spawns, the placeholder is not drawn at random so
+        // seeded games hash the same from the start
+        let running_shape = generate_shape(ShapeType::Square);
+        let next_shape_type = ShapeType::Random;
+        Ok(Board {
+            width,
+            height,
+            buffer_height,
+            cells,
+            spawn_column: None,
+            scoring: ScoringSystem::Lines,
+            rotation_system: RotationSystem::Nintendo,
+            hold_enabled: true,
+            running_shape,
+            next_shape_type,
+            score,
+            lines: 0,
+            pieces_placed: 0,
+            piece_counts: [0; 7],
+            garbage_cleared: 0,
+            elapsed_ms: 0,
+            hold_shape_type: None,
+            hold_used: false,
+            last_move_rotation: false,
+            t_spin: false,
+            game_over_reason: None,
+            history: History::new(),
+            events: Board::new_event_queue(),
+            finesse: Finesse::new(),
+            mode: ModeState::new(),
+            puzzle: None,
+            timing: Timing::new(),
+            versus: VersusState::new(),
+            rng: SeededRng::from_entropy(),
+        })
+    }
+
+    // a copy to search or score placements on, the undo history is left behind and not recorded
+    pub(crate) fn clone_without_history(&self) -> Board {
+        Board {
+            width: self.width,
+            height: self.height,
+            buffer_height: self.buffer_height,
+            cells: self.cells.clone(),
+            spawn_column: self.spawn_column,
+            scoring: self.scoring,
+            rotation_system: self.rotation_system,
+            hold_enabled: self.hold_enabled,
+            running_shape: self.running_shape.clone(),
+            next_shape_type: self.next_shape_type,
+            score: self.score,
+            lines: self.lines,
+            pieces_placed: self.pieces_placed,
+            piece_counts: self.piece_counts,
+            garbage_cleared: self.garbage_cleared,
+            elapsed_ms: self.elapsed_ms,
+            hold_shape_type: self.hold_shape_type,
+            hold_used: self.hold_used,
+            last_move_rotation: self.last_move_rotation,
+            t_spin: self.t_spin,
+            game_over_reason: self.game_over_reason,
+            history: History::disabled(self.get_undo_depth()),
+            events: self.events.clone(),
+            finesse: self.finesse.clone(),
+            mode: self.mode.clone(),
+            puzzle: self.puzzle.clone(),
+            timing: self.timing.clone(),
+            versus: self.versus.clone(),
+            rng: self.rng.clone(),
+        }
+    }
+
     pub fn get_cells(&self) -> &Vec<Vec<Cell>> {
         &self.cells
     }
@@ -206,6 +526,58 @@ impl Board {
         self.score = score;
     }
 
+    pub fn set_lines(&mut self, lines: u32) {
+        self.lines = lines;
+    }
+
+    pub fn set_pieces_placed(&mut self, pieces_placed: u32) {
+        self.pieces_placed = pieces_placed;
+    }
+
+    pub fn get_piece_counts(&self) -> &[u32; 7] {
+        &self.piece_counts
+    }
+
+    pub fn set_piece_counts(&mut self, piece_counts: [u32; 7]) {
+        self.piece_counts = piece_counts;
+    }
+
+    pub fn set_garbage_cleared(&mut self, garbage_cleared: u32) {
+        self.garbage_cleared = garbage_cleared;
+    }
+
+    pub fn set_elapsed_ms(&mut self, elapsed_ms: u32) {
+        self.elapsed_ms = elapsed_ms;
+    }
+
+    pub fn set_hold_shape_type(&mut self, hold_shape_type: Option<ShapeType>) {
+        self.hold_shape_type = hold_shape_type;
+    }
+
+    pub fn is_hold_used(&self) -> bool {
+        self.hold_used
+    }
+
+    pub fn set_hold_used(&mut self, hold_used: bool) {
+        self.hold_used = hold_used;
+    }
+
+    pub fn is_last_move_rotation(&self) -> bool {
+        self.last_move_rotation
+    }
+
+    pub fn set_last_move_rotation(&mut self, last_move_rotation: bool) {
+        self.last_move_rotation = last_move_rotation;
+    }
+
+    pub fn set_t_spin(&mut self, t_spin: bool) {
+        self.t_spin = t_spin;
+    }
+
+    pub fn set_game_over_reason(&mut self, game_over_reason: Option<GameOverReason>) {
+        self.game_over_reason = game_over_reason;
+    }
+
     pub fn get_running_shape(&self) -> &Shape {
         &self.running_shape
     }
@@ -233,20 +605,61 @@ impl Board {
         self.running_shape.top_left_offset = next_top_left_offset;
     }
 
+    // the next shape to spawn, random shapes come from the board's own generator
+    pub fn generate_next_shape(&mut self) -> Shape {
+        // puzzles have a fixed sequence instead
+        if let Some(shape_type) = self.next_puzzle_piece() {
+            return generate_shape(shape_type);
+        }
+        match self.next_shape_type {
+            ShapeType::Random => generate_shape(self.rng.gen()),
+            shape_type => generate_shape(shape_type),
+        }
+    }
+
     pub fn add_shape(&mut self, shape: Shape) {
-        // add a shape into the board, which should appear in the middle of the top row
+        // add a shape into the board, which should appear right above the visible field
+        // the shape is centered horizontally, rounding to the left when it cannot be exactly centered,
+        // unless the rules set a spawn column
+        let shape_rows = shape.data.iter().map(|&(x, _)| x).max().unwrap_or(0) as usize + 1;
+        let top_row = self.buffer_height.saturating_sub(shape_rows);
+        let min_y = shape.data.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        let max_y = shape.data.iter().map(|&(_, y)| y).max().unwrap_or(0);
+        let shape_width = max_y - min_y + 1;
+        let left_column = match self.spawn_column {
+            Some(spawn_column) => (spawn_column as i32).min(self.width as i32 - shape_width),
+            None => (self.width as i32 - shape_width) / 2,
+        };
 
         let mut next = self.cells.clone();
         let mut next_running_cells: PositionSet = vec![];
         for (delta_x, delta_y) in &shape.data {
-            let x = *delta_x as usize;
-            let y = ((self.width as i32 - 1) / 2 + *delta_y) as usize;
+            let x = top_row + *delta_x as usize;
+            let y = (left_column + *delta_y - min_y) as usize;
             next_running_cells.push((x, y));
-            next[x][y] = Cell::Running;
         }
+        let shape_type = shape.shape_type;
         self.set_running_shape(shape);
+        self.reset_piece_inputs();
+
+        // block out, the stack must not be overwritten by the new shape
+        if next_running_cells
+            .iter()
+            .any(|&(x, y)| next[x][y] != Cell::Empty)
+        {
+            self.set_running_cells(vec![]);
+            self.top_out(GameOverReason::BlockOut);
+            return;
+        }
+
+        for (x, y) in next_running_cells.iter().cloned() {
+            next[x][y] = Cell::Running;
+        }
         self.set_running_cells(next_running_cells);
+        self.set_last_move_rotation(false);
         self.cells = next;
+        self.push_event(GameEvent::new(EventKind::Spawn, shape_type));
+        self.record_spawn();
     }
 
     // to check whether one row or column cells are all in one state
@@ -268,17 +681,22 @@ impl Board {
 }
 
 impl fmt::Display for Board {
+    // the alternate flag `{:#}` also prints the hidden buffer rows
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for line in self.cells.as_slice() {
+        let first_row = if f.alternate() { 0 } else { self.buffer_height };
+        for (i, line) in self.cells.iter().enumerate().skip(first_row) {
+            let clearing = self.is_clearing_row(i);
             for &pos in line.as_slice() {
                 let symbol = match pos {
+                    _ if clearing => "|=|",
                     Cell::Placed => "|x|",
+                    Cell::Garbage => "|#|",
                     Cell::Running => "|*|",
                     _ => "| |",
                 };
-                write!(f, "{}", symbol);
+                write!(f, "{}", symbol)?;
             }
-            writeln!(f);
+            writeln!(f)?;
         }
         Ok(()) // success result
     }
@@ -290,12 +708,60 @@ mod tests {
 
     #[test]
     fn test_board_init() {
-        //        let board = Board::new(1, 1);
-        //        assert!(board.is_err(), "Invalid param");
+        let board = Board::try_new(1, 1, 0);
+        assert!(board.is_err(), "Invalid param");
         let board = Board::new(10, 30);
         assert_eq!(board.height, 30);
     }
 
+    #[test]
+    fn test_board_size_limits() {
+        assert!(Board::try_new(4, 4, 0).is_ok());
+        assert!(Board::try_new(100, 900, 100).is_ok());
+        assert_eq!(
+            Board::try_new(3, 20, 0).err(),
+            Some(TetrisError::WidthOutOfRange)
+        );
+        assert_eq!(
+            Board::try_new(101, 20, 0).err(),
+            Some(TetrisError::WidthOutOfRange)
+        );
+        assert_eq!(
+            Board::try_new(10, 3, 0).err(),
+            Some(TetrisError::HeightOutOfRange)
+        );
+        assert_eq!(
+            Board::try_new(10, 3, 1).map(|board| board.get_height()),
+            Ok(4)
+        );
+        assert_eq!(
+            Board::try_new(10, 1000, 1).err(),
+            Some(TetrisError::HeightOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_spawn_column() {
+        fn spawn_columns(width: usize, shape_type: ShapeType) -> Vec<usize> {
+            let mut board = Board::try_new(width, 10, 0).unwrap();
+            board.add_shape(generate_shape(shape_type));
+            let mut columns: Vec<usize> =
+                board.get_running_cells().iter().map(|&(_, y)| y).collect();
+            columns.sort();
+            columns.dedup();
+            columns
+        }
+
+        assert_eq!(spawn_columns(10, ShapeType::T), vec![3, 4, 5]);
+        assert_eq!(spawn_columns(10, ShapeType::Square), vec![4, 5]);
+        assert_eq!(spawn_columns(10, ShapeType::Line), vec![4]);
+        assert_eq!(spawn_columns(9, ShapeType::T), vec![3, 4, 5]);
+        assert_eq!(spawn_columns(9, ShapeType::Square), vec![3, 4]);
+        assert_eq!(spawn_columns(4, ShapeType::L), vec![0, 1, 2]);
+        assert_eq!(spawn_columns(4, ShapeType::Square), vec![1, 2]);
+        assert_eq!(spawn_columns(4, ShapeType::Line), vec![1]);
+    }
+
     #[test]
     fn test_add_shape_square() {
         let mut board = Board::new(8, 10);
@@ -306,6 +772,78 @@ mod tests {
         assert_eq!(*board.get_cell(1, 4), Cell::Running);
     }
 
+    #[test]
+    fn test_board_with_buffer() {
+        let board = Board::try_new(10, 20, 20).unwrap();
+        assert_eq!(board.get_height(), 40);
+        assert_eq!(board.get_buffer_height(), 20);
+        assert_eq!(board.get_visible_height(), 20);
+        assert_eq!(Board::guideline().get_height(), 40);
+    }
+
+    #[test]
+    fn test_render_hides_buffer() {
+        let mut board = Board::try_new(5, 10, 2).unwrap();
+        board.add_shape(generate_shape(ShapeType::Square));
+        assert_eq!(board.render().lines().count(), 10);
+        assert!(!board.render().contains("|*|"));
+        assert_eq!(board.render_with_buffer().lines().count(), 12);
+        assert_eq!(
+            board.render_with_buffer().lines().next(),
+            Some("| ||*||*|| || |")
+        );
+
+        board.set_cell(2, 0, Cell::Placed);
+        let cells = board.get_visible_cells();
+        assert_eq!(cells.len(), 50);
+        assert_eq!(cells[0], Cell::Placed as u8);
+        assert!(cells[1..].iter().all(|&cell| cell == Cell::Empty as u8));
+    }
+
+    #[test]
+    fn test_add_shape_above_visible_field() {
+        let mut board = Board::try_new(8, 10, 4).unwrap();
+        board.add_shape(generate_shape(ShapeType::Square));
+        assert_eq!(*board.get_cell(2, 3), Cell::Running);
+        assert_eq!(*board.get_cell(3, 4), Cell::Running);
+
+        let mut board = Board::try_new(8, 10, 4).unwrap();
+        board.add_shape(generate_shape(ShapeType::Line));
+        assert_eq!(*board.get_cell(0, 3), Cell::Running);
+        assert_eq!(*board.get_cell(3, 3), Cell::Running);
+    }
+
+    #[test]
+    fn test_add_shape_block_out() {
+        let mut board = Board::new(8, 10);
+        board.set_cell(1, 3, Cell::Placed);
+        board.add_shape(generate_shape(ShapeType::Square));
+        assert_eq!(*board.get_cell(0, 3), Cell::Empty);
+        assert_eq!(*board.get_cell(1, 3), Cell::Placed);
+        assert_eq!(*board.get_cell(1, 4), Cell::Empty);
+        assert!(board.get_running_cells().is_empty());
+        assert_eq!(board.get_game_over_reason(), Some(GameOverReason::BlockOut));
+    }
+
+    #[test]
+    fn test_seeded_shapes() {
+        fn shape_types(seed: u32) -> Vec<ShapeType> {
+            let mut board = Board::new(10, 20);
+            board.set_seed(seed);
+            (0..20)
+                .map(|_| board.generate_next_shape().shape_type)
+                .collect()
+        }
+        assert_eq!(shape_types(7), shape_types(7));
+        assert_ne!(shape_types(7), shape_types(8));
+        // every one of the 7 shapes comes up
+        let mut drawn = shape_types(7);
+        drawn.extend(shape_types(8));
+        drawn.sort_by_key(|&shape_type| shape_type as u8);
+        drawn.dedup();
+        assert_eq!(drawn.len(), 7);
+    }
+
     #[test]
     fn test_add_shape_s() {
         let mut board = Board::new(8, 10);
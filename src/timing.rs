@@ -0,0 +1,583 @@
+use crate::action::Direction;
+use crate::hash::StateHasher;
+use crate::tetris::*;
+use wasm_bindgen::prelude::*;
+
+// the engine runs at 60 frames per second
+pub const FRAMES_PER_SECOND: u32 = 60;
+// gravity is counted in 1/256 rows per frame, so 256 is one row every frame (1G)
+pub const GRAVITY_UNIT: u32 = 256;
+// about one row every half second
+pub const DEFAULT_GRAVITY: u32 = 8;
+// 20G, shapes fall to the bottom right away
+pub const INSTANT_GRAVITY: u32 = 20 * GRAVITY_UNIT;
+// moves kept while waiting for the next shape
+const MAX_BUFFERED_MOVES: usize = 8;
+
+// guideline speed curve, the time per row gets shorter with every level
+pub fn gravity_for_level(level: u32) -> u32 {
+    let level = f64::from(level.max(1) - 1);
+    let seconds_per_row = (0.8 - level * 0.007).powf(level);
+    let gravity = f64::from(GRAVITY_UNIT) / (seconds_per_row * f64::from(FRAMES_PER_SECOND));
+    (gravity.round() as u32).max(1)
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Timing {
+    gravity: u32,
+    // gravity is multiplied by this while soft dropping, 0 when not soft dropping
+    soft_drop_factor: u32,
+    // accumulated gravity of the running shape, drops a row every GRAVITY_UNIT
+    gravity_progress: u32,
+    // delays in frames: entry delay (ARE) before a shape spawns, extra delay when rows are cleared,
+    // and how long a shape can rest on the stack before it locks (0 locks on the next row of gravity)
+    are: u32,
+    line_clear_delay: u32,
+    lock_delay: u32,
+    // full rows stay on the board until the line clear delay is over
+    clearing_rows: Vec<usize>,
+    line_clear_left: u32,
+    // frames left before the next shape spawns
+    entry_delay_left: u32,
+    // sideways moves made during the delays, applied to the next shape
+    buffered_moves: Vec<Direction>,
+    // initial rotation and hold, rotate or hold during the delays to spawn already rotated or held
+    irs_enabled: bool,
+    ihs_enabled: bool,
+    buffered_rotations: u32,
+    buffered_hold: bool,
+    // frames the running shape has been resting on the stack
+    lock_frames: u32,
+    // milliseconds not yet turned into a frame, multiplied by FRAMES_PER_SECOND
+    frame_remainder: u64,
+    frame_count: u32,
+}
+
+impl Timing {
+    pub(crate) fn new() -> Timing {
+        Timing {
+            gravity: DEFAULT_GRAVITY,
+            soft_drop_factor: 0,
+            gravity_progress: 0,
+            are: 0,
+            line_clear_delay: 0,
+            lock_delay: 0,
+            clearing_rows: vec![],
+            line_clear_left: 0,
+            entry_delay_left: 0,
+            buffered_moves: vec![],
+            irs_enabled: false,
+            ihs_enabled: false,
+            buffered_rotations: 0,
+            buffered_hold: false,
+            lock_frames: 0,
+            frame_remainder: 0,
+            frame_count: 0,
+        }
+    }
+
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        for &value in [
+            self.gravity,
+            self.soft_drop_factor,
+            self.gravity_progress,
+            self.are,
+            self.line_clear_delay,
+            self.lock_delay,
+            self.line_clear_left,
+            self.entry_delay_left,
+            self.buffered_rotations,
+            self.lock_frames,
+            self.frame_count,
+        ]
+        .iter()
+        {
+            hasher.write_u32(value);
+        }
+        hasher.write_usize(self.clearing_rows.len());
+        for &row in self.clearing_rows.iter() {
+            hasher.write_usize(row);
+        }
+        hasher.write_usize(self.buffered_moves.len());
+        for &direction in self.buffered_moves.iter() {
+            hasher.write_u8(direction as u8);
+        }
+        hasher.write_bool(self.irs_enabled);
+        hasher.write_bool(self.ihs_enabled);
+        hasher.write_bool(self.buffered_hold);
+        hasher.write_u64(self.frame_remainder);
+    }
+}
+
+#[wasm_bindgen]
+impl Board {
+    // advance the game by the time passed since the last update, return false once finished
+    pub fn update(&mut self, delta_ms: u32) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        // a timed mode must not run past its limit
+        let delta_ms = match self.get_time_left_ms() {
+            Some(time_left) => delta_ms.min(time_left),
+            None => delta_ms,
+        };
+        self.set_elapsed_ms(self.get_elapsed_ms() + delta_ms);
+
+        self.timing.frame_remainder += u64::from(delta_ms) * u64::from(FRAMES_PER_SECOND);
+        let frames = self.timing.frame_remainder / 1000;
+        self.timing.frame_remainder %= 1000;
+        for _ in 0..frames {
+            if !self.step_frame() {
+                break;
+            }
+        }
+
+        self.check_mode_goal();
+        !self.is_finished()
+    }
+
+    pub fn get_gravity(&self) -> u32 {
+        self.timing.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: u32) {
+        self.timing.gravity = gravity;
+    }
+
+    pub fn get_soft_drop_factor(&self) -> u32 {
+        self.timing.soft_drop_factor
+    }
+
+    // soft drop without locking, until set back to 0
+    pub fn set_soft_drop_factor(&mut self, soft_drop_factor: u32) {
+        self.timing.soft_drop_factor = soft_drop_factor;
+    }
+
+    pub fn get_are(&self) -> u32 {
+        self.timing.are
+    }
+
+    pub fn set_are(&mut self, are: u32) {
+        self.timing.are = are;
+    }
+
+    pub fn get_line_clear_delay(&self) -> u32 {
+        self.timing.line_clear_delay
+    }
+
+    pub fn set_line_clear_delay(&mut self, line_clear_delay: u32) {
+        self.timing.line_clear_delay = line_clear_delay;
+    }
+
+    pub fn get_lock_delay(&self) -> u32 {
+        self.timing.lock_delay
+    }
+
+    pub fn set_lock_delay(&mut self, lock_delay: u32) {
+        self.timing.lock_delay = lock_delay;
+    }
+
+    // true while waiting for the next shape to spawn, including the line clear delay
+    pub fn is_in_entry_delay(&self) -> bool {
+        self.timing.entry_delay_left > 0 || self.timing.line_clear_left > 0
+    }
+
+    pub fn is_irs_enabled(&self) -> bool {
+        self.timing.irs_enabled
+    }
+
+    pub fn set_irs_enabled(&mut self, enabled: bool) {
+        self.timing.irs_enabled = enabled;
+        self.timing.buffered_rotations = 0;
+    }
+
+    pub fn is_ihs_enabled(&self) -> bool {
+        self.timing.ihs_enabled
+    }
+
+    pub fn set_ihs_enabled(&mut self, enabled: bool) {
+        self.timing.ihs_enabled = enabled;
+        self.timing.buffered_hold = false;
+    }
+
+    // full rows waiting for the line clear delay, to animate them
+    pub fn get_clearing_rows(&self) -> Vec<u32> {
+        self.timing
+            .clearing_rows
+            .iter()
+            .map(|&i| i as u32)
+            .collect()
+    }
+
+    pub fn get_frame_count(&self) -> u32 {
+        self.timing.frame_count
+    }
+}
+
+impl Board {
+    // one frame of the game, return false once finished
+    fn step_frame(&mut self) -> bool {
+        self.timing.frame_count += 1;
+        if self.timing.line_clear_left > 0 {
+            self.timing.line_clear_left -= 1;
+            if self.timing.line_clear_left > 0 {
+                return true;
+            }
+            self.timing.clearing_rows.clear();
+            self.clear_rows();
+            if self.is_finished() || !self.start_entry_delay() {
+                return !self.is_finished();
+            }
+        } else if self.timing.entry_delay_left > 0 {
+            self.timing.entry_delay_left -= 1;
+            if self.timing.entry_delay_left > 0 {
+                return true;
+            }
+            self.spawn_buffered();
+        } else if self.get_running_cells().is_empty() && !self.start_line_clear() {
+            return !self.is_finished();
+        }
+        if self.is_finished() {
+            return false;
+        }
+        self.apply_gravity()
+    }
+
+    // the running shape is locked, or the game just started: full rows wait for the line clear delay
+    // return true if the next shape spawned right away
+    fn start_line_clear(&mut self) -> bool {
+        self.timing.gravity_progress = 0;
+        self.timing.lock_frames = 0;
+        let full_rows = self.get_full_rows();
+        if !full_rows.is_empty() && self.timing.line_clear_delay > 0 {
+            self.timing.clearing_rows = full_rows;
+            self.timing.line_clear_left = self.timing.line_clear_delay;
+            return false;
+        }
+        self.clear_rows();
+        !self.is_finished() && self.start_entry_delay()
+    }
+
+    // return true if the next shape spawned right away
+    fn start_entry_delay(&mut self) -> bool {
+        if self.timing.are == 0 {
+            self.spawn_buffered();
+            true
+        } else {
+            self.timing.entry_delay_left = self.timing.are;
+            false
+        }
+    }
+
+    fn spawn_buffered(&mut self) {
+        self.spawn_next_shape();
+        // the rotation is skipped if the shape has no room for it
+        for _ in 0..self.timing.buffered_rotations {
+            self.rotate();
+        }
+        self.timing.buffered_rotations = 0;
+        let moves: Vec<Direction> = self.timing.buffered_moves.drain(..).collect();
+        for direction in moves {
+            self.move_shape(direction);
+        }
+    }
+
+    // only sideways moves are kept, a drop has nothing to drop until the shape spawns
+    pub(crate) fn buffer_move(&mut self, direction: Direction) {
+        if !self.is_in_entry_delay()
+            || direction == Direction::Down
+            || self.timing.buffered_moves.len() >= MAX_BUFFERED_MOVES
+        {
+            return;
+        }
+        self.timing.buffered_moves.push(direction);
+    }
+
+    pub(crate) fn buffer_rotation(&mut self) {
+        if self.timing.irs_enabled && self.is_in_entry_delay() {
+            self.timing.buffered_rotations = (self.timing.buffered_rotations + 1) % 4;
+        }
+    }
+
+    pub(crate) fn buffer_hold(&mut self) {
+        if self.timing.ihs_enabled && self.is_in_entry_delay() {
+            self.timing.buffered_hold = true;
+        }
+    }
+
+    // the initial hold is used up by the next spawn
+    pub(crate) fn take_buffered_hold(&mut self) -> bool {
+        let buffered_hold = self.timing.buffered_hold;
+        self.timing.buffered_hold = false;
+        buffered_hold
+    }
+
+    pub(crate) fn is_clearing_row(&self, row: usize) -> bool {
+        self.timing.clearing_rows.contains(&row)
+    }
+
+    fn apply_gravity(&mut self) -> bool {
+        let rows = if self.timing.gravity >= INSTANT_GRAVITY {
+            self.timing.gravity_progress = 0;
+            self.get_height()
+        } else {
+            let gravity = match self.timing.soft_drop_factor {
+                0 => self.timing.gravity,
+                factor => self.timing.gravity.saturating_mul(factor),
+            };
+            self.timing.gravity_progress += gravity.min(INSTANT_GRAVITY);
+            let rows = self.timing.gravity_progress / GRAVITY_UNIT;
+            self.timing.gravity_progress %= GRAVITY_UNIT;
+            rows as usize
+        };
+
+        let mut blocked = false;
+        for _ in 0..rows {
+            if !self.can_drop() {
+                blocked = true;
+                break;
+            }
+            self.drop();
+            // stepping down a row resets the lock delay
+            self.timing.lock_frames = 0;
+        }
+        if self.can_drop() {
+            return true;
+        }
+
+        // resting on the stack
+        if self.timing.lock_delay == 0 {
+            if !blocked {
+                return true;
+            }
+        } else {
+            self.timing.lock_frames += 1;
+            if self.timing.lock_frames < self.timing.lock_delay {
+                return true;
+            }
+        }
+        self.drop();
+        if self.is_finished() {
+            return false;
+        }
+        self.start_line_clear();
+        !self.is_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventKind;
+
+    #[test]
+    fn test_update_counts_frames() {
+        let mut board = Board::new(8, 10);
+        board.update(1000);
+        assert_eq!(board.get_frame_count(), 60);
+        board.update(10);
+        assert_eq!(board.get_frame_count(), 60);
+        board.update(7);
+        assert_eq!(board.get_frame_count(), 61);
+        assert_eq!(board.get_elapsed_ms(), 1017);
+    }
+
+    #[test]
+    fn test_gravity() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.set_gravity(GRAVITY_UNIT / 2);
+        // spawn on the first frame, then one row every two frames
+        board.update(17);
+        assert_eq!(*board.get_cell(0, 3), Cell::Running);
+        board.update(33);
+        assert_eq!(*board.get_cell(0, 3), Cell::Empty);
+        assert_eq!(*board.get_cell(2, 3), Cell::Running);
+
+        // several rows in one frame
+        board.set_gravity(GRAVITY_UNIT * 3);
+        board.update(17);
+        assert_eq!(*board.get_cell(5, 3), Cell::Running);
+    }
+
+    #[test]
+    fn test_default_gravity() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.update(17);
+        board.move_shape(Direction::Left);
+        board.update(1000);
+        // 60 frames at 8/256 rows per frame
+        assert_eq!(*board.get_cell(0, 2), Cell::Empty);
+        assert_eq!(*board.get_cell(2, 2), Cell::Running);
+        assert_eq!(*board.get_cell(3, 2), Cell::Empty);
+    }
+
+    #[test]
+    fn test_gravity_for_level() {
+        // one second per row
+        assert_eq!(gravity_for_level(1), 4);
+        assert!(gravity_for_level(2) > gravity_for_level(1));
+        // about 2.4G at level 15
+        assert_eq!(gravity_for_level(15) / GRAVITY_UNIT, 2);
+    }
+
+    #[test]
+    fn test_instant_gravity() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.set_gravity(INSTANT_GRAVITY);
+        board.set_lock_delay(30);
+        board.update(17);
+        assert_eq!(*board.get_cell(9, 3), Cell::Running);
+        board.move_shape(Direction::Left);
+        assert_eq!(*board.get_cell(9, 2), Cell::Running);
+    }
+
+    #[test]
+    fn test_lock_delay() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.set_gravity(INSTANT_GRAVITY);
+        board.set_lock_delay(3);
+        // spawn and land, then rest for 2 more frames
+        board.update(17);
+        board.update(17);
+        assert_eq!(*board.get_cell(9, 3), Cell::Running);
+        board.update(17);
+        assert_eq!(*board.get_cell(9, 3), Cell::Placed);
+        // the next shape spawns right away and lands on the first one
+        assert_eq!(*board.get_cell(0, 3), Cell::Running);
+        board.update(17);
+        assert_eq!(*board.get_cell(7, 3), Cell::Running);
+    }
+
+    #[test]
+    fn test_entry_delay() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.set_are(3);
+        board.set_line_clear_delay(5);
+        // the game starts with an entry delay too
+        board.update(50);
+        assert!(board.is_in_entry_delay());
+        assert!(board.get_running_cells().is_empty());
+        board.update(17);
+        assert!(!board.is_in_entry_delay());
+        assert_eq!(*board.get_cell(0, 3), Cell::Running);
+
+        // a hard drop without clearing rows
+        board.move_shape(Direction::Down);
+        board.update(50);
+        assert!(board.is_in_entry_delay());
+        board.update(17);
+        assert_eq!(*board.get_cell(0, 3), Cell::Running);
+
+        // clearing rows adds the line clear delay
+        for j in 0..8 {
+            if j != 3 && j != 4 {
+                board.set_cell(6, j, Cell::Placed);
+                board.set_cell(7, j, Cell::Placed);
+            }
+        }
+        board.move_shape(Direction::Down);
+        board.update(50);
+        // the full rows stay until the line clear delay is over
+        assert_eq!(board.get_clearing_rows(), vec![6, 7]);
+        assert_eq!(*board.get_cell(7, 0), Cell::Placed);
+        assert_eq!(board.get_lines(), 0);
+        assert_eq!(
+            board.render().lines().nth(7),
+            Some("|=||=||=||=||=||=||=||=|")
+        );
+        board.update(67);
+        assert!(board.is_in_entry_delay());
+        assert!(board.get_clearing_rows().is_empty());
+        assert_eq!(board.get_lines(), 2);
+        board.update(50);
+        assert!(!board.is_in_entry_delay());
+        assert_eq!(*board.get_cell(0, 3), Cell::Running);
+    }
+
+    #[test]
+    fn test_buffered_moves() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.set_are(6);
+        board.update(50);
+        board.move_shape(Direction::Left);
+        board.move_shape(Direction::Left);
+        board.move_shape(Direction::Down);
+        board.update(67);
+        assert_eq!(*board.get_cell(0, 1), Cell::Running);
+        assert_eq!(*board.get_cell(0, 3), Cell::Empty);
+
+        // moves outside of the delays are not kept
+        board.move_shape(Direction::Down);
+        board.move_shape(Direction::Right);
+        board.tick();
+        assert_eq!(*board.get_cell(0, 3), Cell::Running);
+        assert_eq!(*board.get_cell(0, 5), Cell::Empty);
+    }
+
+    #[test]
+    fn test_initial_rotation() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Line);
+        board.set_are(6);
+        board.update(50);
+        // disabled by default
+        board.rotate();
+        board.update(67);
+        assert_eq!(board.get_running_cells().len(), 4);
+        let spawned = board.get_cells().clone();
+
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Line);
+        board.set_are(6);
+        board.set_irs_enabled(true);
+        board.update(50);
+        board.rotate();
+        board.update(67);
+        assert_ne!(*board.get_cells(), spawned);
+        assert_eq!(
+            board.take_events().last().unwrap().get_kind(),
+            EventKind::Rotate
+        );
+    }
+
+    #[test]
+    fn test_initial_hold() {
+        let mut board = Board::new(8, 10);
+        board.set_next_shape_type(ShapeType::Square);
+        board.set_are(6);
+        board.set_ihs_enabled(true);
+        board.update(50);
+        board.hold();
+        board.update(67);
+        assert_eq!(board.get_hold_shape_type(), Some(ShapeType::Square));
+        assert!(board.is_hold_used());
+        assert_eq!(board.get_running_cells().len(), 4);
+
+        // the next spawn is not held again
+        board.move_shape(Direction::Down);
+        board.update(133);
+        assert_eq!(*board.get_cell(0, 3), Cell::Running);
+        assert!(!board.is_hold_used());
+    }
+
+    #[test]
+    fn test_update_until_game_over() {
+        let mut board = Board::new(8, 10);
+        board.set_gravity(GRAVITY_UNIT * 20);
+        let mut updates = 0;
+        while board.update(17) {
+            updates += 1;
+            assert!(updates < 100);
+        }
+        assert!(board.is_game_over());
+        let elapsed = board.get_elapsed_ms();
+        assert!(!board.update(17));
+        assert_eq!(board.get_elapsed_ms(), elapsed);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::ai::{Ai, Heuristic, WEIGHTS};
+use crate::sim::{Simulation, Summary};
+use crate::tetris::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::f64::consts::PI;
+
+// cross-entropy method: sample heuristics around a mean, keep the best ones and move the mean and
+// the spread towards them
+#[derive(Debug, Clone)]
+pub struct Tuner {
+    // every candidate plays the same seeded games
+    pub simulation: Simulation,
+    pub population: usize,
+    // candidates kept from each generation
+    pub elite: usize,
+    pub generations: u32,
+    pub initial_std: f64,
+    // added to the spread so it does not collapse too early
+    pub noise: f64,
+    pub seed: u64,
+}
+
+impl Default for Tuner {
+    fn default() -> Self {
+        Tuner {
+            simulation: Simulation {
+                games: 4,
+                max_pieces: 500,
+                ..Simulation::default()
+            },
+            population: 30,
+            elite: 6,
+            generations: 20,
+            initial_std: 5.0,
+            noise: 0.5,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generation {
+    pub index: u32,
+    pub best: Heuristic,
+    // average lines of the best candidate and of the elite
+    pub best_lines: f64,
+    pub elite_lines: f64,
+    pub mean: Heuristic,
+}
+
+impl Tuner {
+    // tune from the start weights, `report` is called after every generation
+    pub fn run(
+        &self,
+        start: Heuristic,
+        mut report: impl FnMut(&Generation),
+    ) -> Result<Generation, TetrisError> {
+        if self.population == 0 || self.elite == 0 || self.elite > self.population {
+            return Err(TetrisError::InvalidParam);
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut mean = start.to_weights();
+        let mut std = [self.initial_std; WEIGHTS];
+        let mut best: Option<Generation> = None;
+        for index in 0..self.generations {
+            let mut candidates = vec![];
+            for _ in 0..self.population {
+                let mut weights = [0.0; WEIGHTS];
+                for (i, weight) in weights.iter_mut().enumerate() {
+                    *weight = mean[i] + std[i] * normal(&mut rng);
+                }
+                let lines = self.fitness(Heuristic::from_weights(&weights))?;
+                candidates.push((lines, weights));
+            }
+            candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).expect("lines are never NaN"));
+            let elite = &candidates[..self.elite];
+
+            let count = self.elite as f64;
+            for i in 0..WEIGHTS {
+                mean[i] = elite.iter().map(|(_, weights)| weights[i]).sum::<f64>() / count;
+                let variance = elite
+                    .iter()
+                    .map(|(_, weights)| (weights[i] - mean[i]).powi(2))
+                    .sum::<f64>()
+                    / count;
+                std[i] = variance.sqrt() + self.noise;
+            }
+
+            let generation = Generation {
+                index,
+                best: Heuristic::from_weights(&elite[0].1),
+                best_lines: elite[0].0,
+                elite_lines: elite.iter().map(|(lines, _)| lines).sum::<f64>() / count,
+                mean: Heuristic::from_weights(&mean),
+            };
+            report(&generation);
+            let improved = match &best {
+                Some(best) => generation.best_lines > best.best_lines,
+                None => true,
+            };
+            if improved {
+                best = Some(generation);
+            }
+        }
+        best.ok_or(TetrisError::InvalidParam)
+    }
+
+    // average lines on the seeded games
+    pub fn fitness(&self, heuristic: Heuristic) -> Result<f64, TetrisError> {
+        let stats = self.simulation.run(move || Ai::with_heuristic(heuristic))?;
+        Ok(Summary::of(&stats).average_lines)
+    }
+}
+
+// standard normal sample, Box-Muller transform
+fn normal(rng: &mut StdRng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiny_tuner() -> Tuner {
+        Tuner {
+            simulation: Simulation {
+                games: 1,
+                max_pieces: 20,
+                threads: 1,
+                ..Simulation::default()
+            },
+            population: 4,
+            elite: 2,
+            generations: 2,
+            ..Tuner::default()
+        }
+    }
+
+    #[test]
+    fn test_weights() {
+        let heuristic = Heuristic::yiyuan_lee();
+        assert_eq!(Heuristic::from_weights(&heuristic.to_weights()), heuristic);
+        assert_eq!(
+            Heuristic::try_from_json(&heuristic.to_json()),
+            Ok(heuristic)
+        );
+        assert_eq!(
+            Heuristic::try_from_json(r#"{"holes": -1.0}"#)
+                .unwrap()
+                .holes,
+            -1.0
+        );
+        assert!(Heuristic::try_from_json(r#"{"holes": "deep"}"#).is_err());
+    }
+
+    #[test]
+    fn test_tuner() {
+        let tuner = tiny_tuner();
+        let mut generations = vec![];
+        let best = tuner
+            .run(Heuristic::el_tetris(), |generation| {
+                generations.push(generation.clone())
+            })
+            .unwrap();
+        assert_eq!(generations.len(), 2);
+        assert!(generations.iter().all(|g| best.best_lines >= g.best_lines));
+        assert!(generations.iter().all(|g| g.best_lines >= g.elite_lines));
+        assert_eq!(tuner.fitness(best.best).unwrap(), best.best_lines);
+
+        // the same seed tunes the same way
+        assert_eq!(tuner.run(Heuristic::el_tetris(), |_| {}).unwrap(), best);
+
+        let invalid = Tuner {
+            elite: 5,
+            ..tiny_tuner()
+        };
+        assert_eq!(
+            invalid.run(Heuristic::el_tetris(), |_| {}).err(),
+            Some(TetrisError::InvalidParam)
+        );
+    }
+}
@@ -0,0 +1,566 @@
+use crate::config::GameConfig;
+use crate::hash::{SeededRng, StateHasher};
+use crate::input::{InputController, Key};
+use crate::tetris::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+
+// what the last locked shape cleared, read by the match to send garbage
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LineClear {
+    pub lines: u32,
+    pub t_spin: bool,
+    pub perfect_clear: bool,
+    // clears in a row, 1 for the first one
+    pub combo: u32,
+    // a tetris or a T-spin right after another one
+    pub back_to_back: bool,
+}
+
+// mixed into the seed of the garbage holes
+const GARBAGE_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+#[derive(Debug, Clone)]
+pub(crate) struct VersusState {
+    combo: u32,
+    // the last clear was a tetris or a T-spin
+    back_to_back: bool,
+    last_clear: Option<LineClear>,
+    // batches of garbage rows sent by the other players, oldest first
+    incoming: VecDeque<u32>,
+    garbage_sent: u32,
+    // draws the garbage holes, apart from the shapes so garbage does not change the next shapes
+    rng: SeededRng,
+}
+
+impl VersusState {
+    pub(crate) fn new() -> VersusState {
+        VersusState {
+            combo: 0,
+            back_to_back: false,
+            last_clear: None,
+            incoming: VecDeque::new(),
+            garbage_sent: 0,
+            rng: SeededRng::from_entropy(),
+        }
+    }
+
+    // another stream than the shapes of the same seed
+    pub(crate) fn set_seed(&mut self, seed: u32) {
+        self.rng = SeededRng::seed_from_u64(u64::from(seed) ^ GARBAGE_SEED);
+    }
+
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.combo);
+        hasher.write_bool(self.back_to_back);
+        hasher.write_usize(self.incoming.len());
+        for &rows in self.incoming.iter() {
+            hasher.write_u32(rows);
+        }
+        hasher.write_u32(self.garbage_sent);
+        self.rng.hash_state(hasher);
+    }
+}
+
+// garbage rows sent for a clear, every field can be left out of the JSON
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttackTable {
+    // by cleared lines, 0 to 4
+    lines: Vec<u32>,
+    // by lines cleared with a T-spin, 0 to 3
+    t_spin: Vec<u32>,
+    // by combo - 1, the last one is used for longer combos
+    combo: Vec<u32>,
+    back_to_back: u32,
+    perfect_clear: u32,
+}
+
+impl Default for AttackTable {
+    fn default() -> Self {
+        AttackTable::guideline()
+    }
+}
+
+#[wasm_bindgen]
+impl AttackTable {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> AttackTable {
+        AttackTable::default()
+    }
+
+    pub fn guideline() -> AttackTable {
+        AttackTable {
+            lines: vec![0, 0, 1, 2, 4],
+            t_spin: vec![0, 2, 4, 6],
+            combo: vec![0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+            back_to_back: 1,
+            perfect_clear: 10,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("attack table is always serializable")
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl AttackTable {
+    pub fn from_json(json: &str) -> Result<AttackTable, JsValue> {
+        AttackTable::try_from_json(json).map_err(JsValue::from)
+    }
+}
+
+impl AttackTable {
+    pub fn try_from_json(json: &str) -> Result<AttackTable, TetrisError> {
+        serde_json::from_str(json).map_err(|_| TetrisError::InvalidConfig)
+    }
+
+    pub fn attack(&self, clear: &LineClear) -> u32 {
+        if clear.lines == 0 {
+            return 0;
+        }
+        let table = if clear.t_spin {
+            &self.t_spin
+        } else {
+            &self.lines
+        };
+        let mut attack = lookup(table, clear.lines as usize);
+        attack += lookup(&self.combo, clear.combo as usize - 1);
+        if clear.back_to_back {
+            attack += self.back_to_back;
+        }
+        if clear.perfect_clear {
+            attack += self.perfect_clear;
+        }
+        attack
+    }
+}
+
+// the last value is used past the end
+fn lookup(table: &[u32], i: usize) -> u32 {
+    table.get(i).or_else(|| table.last()).cloned().unwrap_or(0)
+}
+
+#[wasm_bindgen]
+impl Board {
+    // clears in a row, 0 when the last shape cleared nothing
+    pub fn get_combo(&self) -> u32 {
+        self.versus.combo
+    }
+
+    pub fn is_back_to_back(&self) -> bool {
+        self.versus.back_to_back
+    }
+
+    // garbage rows waiting to be inserted
+    pub fn get_incoming_garbage(&self) -> u32 {
+        self.versus.incoming.iter().sum()
+    }
+
+    pub fn get_garbage_sent(&self) -> u32 {
+        self.versus.garbage_sent
+    }
+}
+
+impl Board {
+    pub fn take_line_clear(&mut self) -> Option<LineClear> {
+        self.versus.last_clear.take()
+    }
+
+    pub fn receive_garbage(&mut self, rows: u32) {
+        if rows > 0 {
+            self.versus.incoming.push_back(rows);
+        }
+    }
+
+    // the incoming garbage cancels the attack first, return what is left of the attack
+    pub fn cancel_garbage(&mut self, mut attack: u32) -> u32 {
+        while attack > 0 {
+            match self.versus.incoming.front_mut() {
+                Some(rows) if *rows > attack => {
+                    *rows -= attack;
+                    attack = 0;
+                }
+                Some(rows) => {
+                    attack -= *rows;
+                    self.versus.incoming.pop_front();
+                }
+                None => break,
+            }
+        }
+        attack
+    }
+
+    // called after the rows of a locked shape are deleted
+    pub(crate) fn record_line_clear(&mut self, deleted_rows: usize) {
+        let lines = deleted_rows as u32;
+        let t_spin = self.is_t_spin();
+        if lines == 0 {
+            self.versus.combo = 0;
+        } else {
+            self.versus.combo += 1;
+        }
+        let difficult = lines >= 4 || (t_spin && lines > 0);
+        let perfect_clear = lines > 0
+            && self
+                .get_cells()
+                .iter()
+                .all(|row| row.iter().all(|&cell| cell == Cell::Empty));
+        self.versus.last_clear = Some(LineClear {
+            lines,
+            t_spin,
+            perfect_clear,
+            combo: self.versus.combo,
+            back_to_back: difficult && self.versus.back_to_back,
+        });
+        if lines > 0 {
+            self.versus.back_to_back = difficult;
+        }
+    }
+
+    // called before a shape spawns, the garbage waits while the player keeps clearing lines
+    pub(crate) fn insert_incoming_garbage(&mut self) {
+        let cleared = self.versus.combo > 0;
+        if cleared || self.versus.incoming.is_empty() {
+            return;
+        }
+        let width = self.get_width();
+        let batches: Vec<u32> = self.versus.incoming.drain(..).collect();
+        for rows in batches {
+            // every batch has its own hole
+            let hole = self.versus.rng.gen_range(0, width as u32) as usize;
+            self.try_insert_garbage(rows as usize, hole)
+                .expect("the hole is on the board");
+            if self.is_finished() {
+                return;
+            }
+        }
+    }
+}
+
+// two or more boards playing against each other, the lines they clear are sent as garbage
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Match {
+    boards: Vec<Board>,
+    inputs: Vec<InputController>,
+    attack_table: AttackTable,
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl Match {
+    #[wasm_bindgen(constructor)]
+    pub fn new(players: usize, config: &GameConfig) -> Result<Match, JsValue> {
+        Match::try_new(players, config).map_err(JsValue::from)
+    }
+}
+
+#[wasm_bindgen]
+impl Match {
+    pub fn get_players(&self) -> usize {
+        self.boards.len()
+    }
+
+    // every board gets the same shapes
+    pub fn set_seed(&mut self, seed: u32) {
+        for board in self.boards.iter_mut() {
+            board.set_seed(seed);
+        }
+    }
+
+    pub fn get_attack_table(&self) -> AttackTable {
+        self.attack_table.clone()
+    }
+
+    pub fn set_attack_table(&mut self, attack_table: AttackTable) {
+        self.attack_table = attack_table;
+    }
+
+    // a copy of the board of the player, which JS has to free, see the getters below for every
+    // frame
+    pub fn get_board(&self, player: usize) -> Board {
+        self.boards[player].clone()
+    }
+
+    pub fn get_score(&self, player: usize) -> i32 {
+        self.boards[player].get_score()
+    }
+
+    pub fn get_incoming_garbage(&self, player: usize) -> u32 {
+        self.boards[player].get_incoming_garbage()
+    }
+
+    pub fn render(&self, player: usize) -> String {
+        self.boards[player].render()
+    }
+
+    pub fn key_down(&mut self, player: usize, key: Key, time_ms: u32) {
+        self.inputs[player].key_down(&mut self.boards[player], key, time_ms);
+        self.send_garbage(player);
+    }
+
+    pub fn key_up(&mut self, player: usize, key: Key, time_ms: u32) {
+        self.inputs[player].key_up(&mut self.boards[player], key, time_ms);
+    }
+
+    // advance every board, return false once the match is over
+    pub fn update(&mut self, delta_ms: u32, time_ms: u32) -> bool {
+        for player in 0..self.boards.len() {
+            self.inputs[player].update(&mut self.boards[player], time_ms);
+            self.boards[player].update(delta_ms);
+            self.send_garbage(player);
+        }
+        !self.is_finished()
+    }
+
+    // the match is over when at most one player is still playing
+    pub fn is_finished(&self) -> bool {
+        self.boards
+            .iter()
+            .filter(|board| !board.is_finished())
+            .count()
+            <= 1
+    }
+
+    // the last player standing, None while playing or when nobody is left
+    pub fn get_winner(&self) -> Option<usize> {
+        if !self.is_finished() {
+            return None;
+        }
+        self.boards.iter().position(|board| !board.is_finished())
+    }
+}
+
+impl Match {
+    pub fn try_new(players: usize, config: &GameConfig) -> Result<Match, TetrisError> {
+        if players < 2 {
+            return Err(TetrisError::InvalidParam);
+        }
+        let mut boards = vec![];
+        for _ in 0..players {
+            let mut board = Board::try_from_config(config)?;
+            // a match cannot be taken back
+            board.set_undo_enabled(false);
+            boards.push(board);
+        }
+        Ok(Match {
+            boards,
+            inputs: vec![InputController::new(); players],
+            attack_table: AttackTable::guideline(),
+        })
+    }
+
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.boards.len());
+        for (board, input) in self.boards.iter().zip(self.inputs.iter()) {
+            board.hash_state(hasher);
+            input.hash_state(hasher);
+        }
+    }
+
+    pub fn board(&self, player: usize) -> &Board {
+        &self.boards[player]
+    }
+
+    pub fn board_mut(&mut self, player: usize) -> &mut Board {
+        &mut self.boards[player]
+    }
+
+    // one tick of every board without the frame timing, see `Board::tick`
+    pub fn tick(&mut self) -> bool {
+        for player in 0..self.boards.len() {
+            self.boards[player].tick();
+            self.send_garbage(player);
+        }
+        !self.is_finished()
+    }
+
+    // turn the last clear of the player into garbage for the next player still playing
+    pub fn send_garbage(&mut self, player: usize) {
+        let clear = match self.boards[player].take_line_clear() {
+            Some(clear) => clear,
+            None => return,
+        };
+        let attack = self.attack_table.attack(&clear);
+        let attack = self.boards[player].cancel_garbage(attack);
+        if attack == 0 {
+            return;
+        }
+        let players = self.boards.len();
+        let target = (1..players)
+            .map(|i| (player + i) % players)
+            .find(|&target| !self.boards[target].is_finished());
+        if let Some(target) = target {
+            self.boards[target].receive_garbage(attack);
+            self.boards[player].versus.garbage_sent += attack;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Direction;
+    use crate::garbage::garbage_row;
+
+    fn clear(lines: u32, combo: u32) -> LineClear {
+        LineClear {
+            lines,
+            t_spin: false,
+            perfect_clear: false,
+            combo,
+            back_to_back: false,
+        }
+    }
+
+    #[test]
+    fn test_attack_table() {
+        let table = AttackTable::guideline();
+        assert_eq!(table.attack(&clear(0, 0)), 0);
+        assert_eq!(table.attack(&clear(1, 1)), 0);
+        assert_eq!(table.attack(&clear(4, 1)), 4);
+        assert_eq!(table.attack(&clear(2, 3)), 1 + 1);
+        assert_eq!(table.attack(&clear(1, 20)), 5);
+        let tsd = LineClear {
+            t_spin: true,
+            back_to_back: true,
+            ..clear(2, 1)
+        };
+        assert_eq!(table.attack(&tsd), 4 + 1);
+        let perfect = LineClear {
+            perfect_clear: true,
+            ..clear(4, 1)
+        };
+        assert_eq!(table.attack(&perfect), 14);
+
+        let table = AttackTable::try_from_json(r#"{"lines": [0, 1, 2, 3, 4]}"#).unwrap();
+        assert_eq!(table.attack(&clear(1, 1)), 1);
+        assert_eq!(table.attack(&clear(4, 1)), 4);
+        assert_eq!(
+            AttackTable::try_from_json(r#"{"lines": 1}"#),
+            Err(TetrisError::InvalidConfig)
+        );
+    }
+
+    #[test]
+    fn test_cancel_garbage() {
+        let mut board = Board::guideline();
+        board.receive_garbage(2);
+        board.receive_garbage(3);
+        assert_eq!(board.get_incoming_garbage(), 5);
+        assert_eq!(board.cancel_garbage(3), 0);
+        assert_eq!(board.get_incoming_garbage(), 2);
+        assert_eq!(board.cancel_garbage(4), 2);
+        assert_eq!(board.get_incoming_garbage(), 0);
+    }
+
+    // fill the bottom rows but the last column, and drop the running line in it, a cell is left
+    // above so it is not a perfect clear
+    fn tetris(board: &mut Board) {
+        let height = board.get_height();
+        for i in height - 4..height {
+            for j in 0..board.get_width() - 1 {
+                board.set_cell(i, j, Cell::Placed);
+            }
+        }
+        board.set_cell(height - 5, 0, Cell::Placed);
+        while board.get_running_cells().iter().any(|&(_, j)| j < 9) {
+            board.move_shape(Direction::Right);
+        }
+        board.move_shape(Direction::Down);
+    }
+
+    #[test]
+    fn test_versus() {
+        let mut versus = Match::try_new(2, &GameConfig::guideline()).unwrap();
+        versus.set_seed(1);
+        for player in 0..2 {
+            versus
+                .board_mut(player)
+                .set_next_shape_type(ShapeType::Line);
+            versus.board_mut(player).tick();
+        }
+        tetris(versus.board_mut(0));
+        versus.board_mut(0).set_next_shape_type(ShapeType::Line);
+        versus.tick();
+        assert_eq!(versus.board(0).get_lines(), 4);
+        assert_eq!(versus.board(0).get_combo(), 1);
+        assert_eq!(versus.board(0).get_garbage_sent(), 4);
+        assert_eq!(versus.board(1).get_incoming_garbage(), 4);
+        assert_eq!(versus.get_incoming_garbage(1), 4);
+        assert_eq!(versus.get_score(0), versus.board(0).get_score());
+        assert!(!versus.board(0).can_undo());
+
+        // the garbage comes in when the other player places a shape without clearing
+        let height = versus.board(1).get_height();
+        versus.board_mut(1).move_shape(Direction::Down);
+        versus.tick();
+        assert_eq!(versus.board(1).get_incoming_garbage(), 0);
+        let rows = &versus.board(1).get_cells()[height - 4..];
+        let hole = rows[0]
+            .iter()
+            .position(|&cell| cell == Cell::Empty)
+            .unwrap();
+        assert!(rows.iter().all(|row| *row == garbage_row(row.len(), hole)));
+
+        // a back to back tetris
+        tetris(versus.board_mut(0));
+        versus.tick();
+        assert!(versus.board(0).is_back_to_back());
+        assert_eq!(versus.board(0).get_garbage_sent(), 4 + 4 + 1);
+        assert!(!versus.is_finished());
+        assert_eq!(versus.get_winner(), None);
+    }
+
+    #[test]
+    fn test_garbage_keeps_the_shapes() {
+        // the holes come from their own generator, both boards still get the same shapes
+        let mut versus = Match::try_new(2, &GameConfig::guideline()).unwrap();
+        versus.set_seed(7);
+        versus.board_mut(1).receive_garbage(2);
+        versus.board_mut(1).receive_garbage(3);
+        let mut shapes = [vec![], vec![]];
+        for _ in 0..5 {
+            for (player, shapes) in shapes.iter_mut().enumerate() {
+                let board = versus.board_mut(player);
+                board.tick();
+                shapes.push(board.get_running_shape().shape_type);
+                board.move_shape(Direction::Down);
+            }
+        }
+        assert_eq!(versus.board(1).get_incoming_garbage(), 0);
+        assert!(!versus.board(1).is_finished());
+        assert_eq!(shapes[0], shapes[1]);
+        assert_eq!(
+            versus.board(0).get_next_shape_type(),
+            versus.board(1).get_next_shape_type()
+        );
+    }
+
+    #[test]
+    fn test_winner() {
+        assert_eq!(
+            Match::try_new(1, &GameConfig::guideline()).err(),
+            Some(TetrisError::InvalidParam)
+        );
+        let mut versus = Match::try_new(3, &GameConfig::guideline()).unwrap();
+        for player in 1..3 {
+            let board = versus.board_mut(player);
+            // a line could clear the garbage through its hole
+            board.set_next_shape_type(ShapeType::Square);
+            board.receive_garbage(40);
+            board.tick();
+            board.move_shape(Direction::Down);
+            board.tick();
+        }
+        versus.tick();
+        assert!(versus.is_finished());
+        assert_eq!(versus.get_winner(), Some(0));
+    }
+}
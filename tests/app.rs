@@ -1,4 +1,7 @@
-use futures::prelude::*;
+use tetris_mk::config::GameConfig;
+use tetris_mk::hash::replay;
+use tetris_mk::input::Key;
+use tetris_mk::net::KeyEvent;
 use wasm_bindgen::JsValue;
 use wasm_bindgen_futures::JsFuture;
 use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
@@ -18,14 +21,61 @@ fn web_test() {
 }
 
 // This runs a unit test in the browser, and in addition it supports asynchronous Future APIs.
-#[wasm_bindgen_test(async)]
-fn async_test() -> impl Future<Item = (), Error = JsValue> {
+#[wasm_bindgen_test]
+async fn async_test() {
     // Creates a JavaScript Promise which will asynchronously resolve with the value 42.
     let promise = js_sys::Promise::resolve(&JsValue::from(42));
 
     // Converts that Promise into a Future.
     // The unit test will wait for the Future to resolve.
-    JsFuture::from(promise).map(|x| {
-        assert_eq!(x, 42);
-    })
+    let x = JsFuture::from(promise).await.unwrap();
+    assert_eq!(x, 42);
+}
+
+// the state hash after the last frame of the replay below, a native and a wasm build must both
+// get it, or networked games between them would go out of sync
+const REPLAY_HASH: u64 = 0xd65f_e7a1_18f4_271c;
+const REPLAY_FRAMES: u32 = 1200;
+
+// every 40 frames a piece is shifted with DAS to either side, rotated, sometimes held, and
+// hard dropped
+fn replay_log() -> Vec<(u32, KeyEvent)> {
+    let mut log = vec![];
+    let mut event = |frame, key, pressed| log.push((frame, KeyEvent { key, pressed }));
+    for piece in 0..REPLAY_FRAMES / 40 {
+        let start = piece * 40;
+        let side = if piece % 2 == 0 {
+            Key::Left
+        } else {
+            Key::Right
+        };
+        event(start, side, true);
+        event(start + 5 + piece % 15, side, false);
+        event(start + 22, Key::Rotate, true);
+        event(start + 23, Key::Rotate, false);
+        if piece % 4 == 3 {
+            event(start + 25, Key::Hold, true);
+            event(start + 26, Key::Hold, false);
+        }
+        event(start + 30, Key::HardDrop, true);
+        event(start + 31, Key::HardDrop, false);
+    }
+    log
+}
+
+fn replay_hashes() -> Vec<u64> {
+    replay(&GameConfig::guideline(), 42, &replay_log(), REPLAY_FRAMES).unwrap()
+}
+
+#[test]
+fn replay_test() {
+    let hashes = replay_hashes();
+    assert_eq!(replay_hashes(), hashes);
+    assert_eq!(hashes[hashes.len() - 1], REPLAY_HASH);
+}
+
+#[wasm_bindgen_test]
+fn web_replay_test() {
+    let hashes = replay_hashes();
+    assert_eq!(hashes[hashes.len() - 1], REPLAY_HASH);
 }